@@ -1,6 +1,6 @@
 #!/usr/bin/env sh
 echo "Building backend..."
-docker build . -t authentra-backend
+docker build . -t authentra-backend --build-arg GIT_COMMIT="$(git rev-parse --short HEAD)"
 cd frontend/
 echo "Building frontend..."
 docker build . -t authentra-frontend
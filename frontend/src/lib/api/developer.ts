@@ -15,7 +15,7 @@ export type InternalScope = {
     description: typeof InternalScopeObj[keyof typeof InternalScopeObj];
 }
 
-export const ApplicationKinds = ['web-server', 'spa'];
+export const ApplicationKinds = ['web-server', 'spa', 'native'];
 export type ApplicationKind = typeof ApplicationKinds[number];
 
 export interface Application {
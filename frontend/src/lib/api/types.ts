@@ -5,4 +5,5 @@ export interface User {
     name: string,
     roles: UserRole[],
     require_password_reset: boolean,
+    avatar: string | null,
 }
\ No newline at end of file
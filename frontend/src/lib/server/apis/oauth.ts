@@ -1,17 +1,24 @@
 import type { Api, ExtendedResponse } from "$lib/api";
-import { InternalScopeObj, type InternalScope, type InternalScopeName } from "$lib/api/developer";
 import { redirect } from "@sveltejs/kit";
 
-export interface EarlyOAuthCheck {
-    app_name: string,
-    invalid_scopes: string[],
-    scopes: InternalScopeName[]
+export interface ConsentApplication {
+    name: string,
+    logo_uri: string | null,
+    publisher: string | null
+}
+
+export interface ConsentScope {
+    name: string,
+    description: string | null,
+    new: boolean
 }
 
 export interface OAuthCheck {
-    app_name: string,
-    invalid_scopes: string[],
-    scopes: InternalScope[]
+    application: ConsentApplication,
+    scopes: ConsentScope[],
+    previously_granted: string[],
+    previously_declined: string[],
+    invalid_scopes: string[]
 }
 
 type OAuthErrorKindCommon = 'invalid_request' | 'unauthorized_client' | 'invalid_scope';
@@ -23,7 +30,8 @@ export type OAuthErrorKind = OAuthErrorKindCommon & OAuthErrorKindAuthorize & OA
 export interface OAuthError {
     error: OAuthErrorKind,
     error_description: string | undefined,
-    error_uri: string | undefined
+    error_uri: string | undefined,
+    correlation_id: string
 }
 
 export type OAuthResponse<T> = ({ success: true } & T) | ({ success: false } & OAuthError) | { success: 'redirect', code: number, location: string, makeRedirect: () => never }
@@ -69,19 +77,10 @@ export class OAuthApi {
         })
     }
 
-    async check(parameters: URLSearchParams): Promise<OAuthResponse<OAuthCheck>> {
-        const res: OAuthResponse<EarlyOAuthCheck> = await this.makeOAuthResponse(this.api.get('/oauth/authorize?' + parameters.toString(), {
+    check(parameters: URLSearchParams): Promise<OAuthResponse<OAuthCheck>> {
+        return this.makeOAuthResponse(this.api.get('/oauth/authorize?' + parameters.toString(), {
             redirect: 'manual'
         }, true));
-        if (res.success === true) {
-            const test = res.scopes.map(scope => { return { name: scope, description: InternalScopeObj[scope] } });
-            //@ts-expect-error
-            res.scopes = test;
-            //@ts-expect-error
-            return res as OAuthResponse<OAuthCheck>
-        } else {
-            return res
-        }
     }
     post(parameters: URLSearchParams): Promise<OAuthResponse<void>> {
         return this.makeOAuthResponse(this.api.post('/oauth/authorize?' + parameters.toString(), {
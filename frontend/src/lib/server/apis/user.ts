@@ -13,6 +13,13 @@ export interface AdminUser {
 
 }
 
+export interface Page<T> {
+    items: T[],
+    total: number,
+    next: string | null,
+    prev: string | null,
+}
+
 export class UserApi {
     private api: Api;
 
@@ -20,8 +27,18 @@ export class UserApi {
         this.api = api
     }
 
-    list(): Promise<AdminUser[]> {
-        return checkResponse(this.api.get('/users')).then(res => res.response)
+    async list(): Promise<AdminUser[]> {
+        const users: AdminUser[] = []
+        let query: string | undefined = undefined
+        do {
+            const page: Page<AdminUser> = await this.page(query)
+            users.push(...page.items)
+            query = page.next ?? undefined
+        } while (query)
+        return users
+    }
+    page(query?: string): Promise<Page<AdminUser>> {
+        return checkResponse<Page<AdminUser>>(this.api.get('/users' + (query ? '?' + query : ''))).then(res => res.response)
     }
     create(name: string, password: string, customer: boolean, roles: UserRole[]): Promise<void> {
         return checkResponse(this.api.post('/users', { ...jsonBody({ name, password, customer, roles }) })).then(res => res.response)
@@ -3,6 +3,7 @@ import { getContext, setContext } from "svelte";
 import { Api } from "./api";
 import { redirect } from "@sveltejs/kit";
 import { building } from "$app/environment";
+import { base } from "$app/paths";
 
 export interface Meta {
     api_token: string | null
@@ -15,7 +16,7 @@ export interface ClientMeta {
 export function extractRedirect(params: URLSearchParams): string {
     const redirect = params.get('redirect')
     if (!redirect) {
-        return '/'
+        return `${base}/`
     }
     return `/${redirect.slice(1)}`
 }
@@ -32,7 +33,7 @@ export function jsonBody(body: any): RequestInit {
 export function redirectUrl(current: URL, target: string, code: 300 | 301 | 302 | 303 | 304 | 305 | 306 | 307 | 308, paramName: string = 'redirect'): URL {
     const params = new URLSearchParams();
     params.set(paramName, `${current.pathname}${current.search}`)
-    throw redirect(code, `${target}?${params.toString()}`);
+    throw redirect(code, `${base}${target}?${params.toString()}`);
 }
 
 export function handleMeta(meta: Meta): ClientMeta {
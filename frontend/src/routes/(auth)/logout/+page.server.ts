@@ -1,12 +1,13 @@
 import type { PageServerLoad } from "./$types";
 import { redirect } from "@sveltejs/kit";
+import { base } from "$app/paths";
 
 export const load: PageServerLoad = async ({ locals, cookies }) => {
     if (!locals.user) {
-        throw redirect(303, '/login')
+        throw redirect(303, `${base}/login`)
     }
     await locals.api.delete('/auth/browser/logout')
     cookies.delete('session_token')
     cookies.delete('jwt')
-    throw redirect(303, '/login')
+    throw redirect(303, `${base}/login`)
 };
\ No newline at end of file
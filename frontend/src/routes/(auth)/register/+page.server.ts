@@ -2,6 +2,7 @@ import { fail, redirect } from "@sveltejs/kit";
 import type { Actions, PageServerLoad } from "./$types";
 import { extractRedirect, jsonBody } from "$lib/utils";
 import type { ApiResponse, ExtendedResponse } from "$lib/api";
+import { base } from "$app/paths";
 
 export const actions: Actions = {
     default: async ({request, locals}) => {
@@ -20,7 +21,7 @@ export const actions: Actions = {
 export const load: PageServerLoad = async ({url, locals}) => {
     const registrationEnabled = await locals.api.get<boolean>('/auth/registration')
     if (registrationEnabled.api && registrationEnabled.api.success && !registrationEnabled.api.response) {
-        throw redirect(303, '/login')
+        throw redirect(303, `${base}/login`)
     }
     if (locals.user) {
         throw redirect(303, extractRedirect(url.searchParams))
@@ -0,0 +1,15 @@
+import { fail } from "@sveltejs/kit";
+import type { Actions } from "./$types";
+
+export const actions: Actions = {
+    default: async ({params, locals}) => {
+        const res = await locals.api.post<null>(`/auth/report/${encodeURIComponent(params.token)}`)
+        if (!res.api) {
+            return fail(res.status, {success: false, message: await res.text()})
+        }
+        if (!res.api.success) {
+            return fail(res.status, {success: false, message: res.api.message})
+        }
+        return {success: true}
+    }
+};
@@ -2,6 +2,7 @@ import { UserRoles, type UserRole } from "$lib/api/types";
 import { getRolesFromForm } from "$lib/server/utils";
 import { redirect } from "@sveltejs/kit";
 import type { Actions, PageServerLoad } from "./$types";
+import { base } from "$app/paths";
 
 export const load: PageServerLoad = async ({params, locals}) => {
     return {
@@ -27,6 +28,6 @@ export const actions: Actions = {
     delete: async ({params, locals}) => {
         console.log("DEleting" +params.id)
         await locals.apis.users.delete(params.id)
-        throw redirect(307, '/admin/users')
+        throw redirect(307, `${base}/admin/users`)
     },
 };
\ No newline at end of file
@@ -1,5 +1,18 @@
 import { redirect } from "@sveltejs/kit";
 import type { Actions, PageServerLoad } from "./$types";
+import { base } from "$app/paths";
+import type { OAuthError } from "$lib/server/apis/oauth";
+
+function errorPage(error: OAuthError): never {
+    const query = new URLSearchParams({
+        error: error.error,
+        correlation_id: error.correlation_id
+    });
+    if (error.error_description) {
+        query.set('error_description', error.error_description);
+    }
+    throw redirect(303, `${base}/oauth/error?${query}`)
+}
 
 export const load: PageServerLoad = async ({url, locals, setHeaders}) => {
     setHeaders({
@@ -11,6 +24,9 @@ export const load: PageServerLoad = async ({url, locals, setHeaders}) => {
     if (data.success == 'redirect') {
         data.makeRedirect()
     }
+    if (data.success == false) {
+        errorPage(data)
+    }
     console.log(data)
     return {
         check: data
@@ -27,6 +43,8 @@ export const actions: Actions = {
         const res = await locals.apis.oauth.post(searchParams);
         if (res.success == 'redirect') {
             res.makeRedirect()
+        } else if (res.success == false) {
+            errorPage(res)
         } else {
             return res
         }
@@ -41,6 +59,8 @@ export const actions: Actions = {
         const res = await locals.apis.oauth.post(searchParams);
         if (res.success == 'redirect') {
             res.makeRedirect()
+        } else if (res.success == false) {
+            errorPage(res)
         } else {
             return res
         }
@@ -20,6 +20,9 @@ const config = {
     // If your environment is not supported or you settled on a specific environment, switch out the adapter.
     // See https://kit.svelte.dev/docs/adapters for more information about adapters.
     adapter: adapter(),
+    paths: {
+      base: process.env.BASE_PATH ?? "",
+    },
   },
 };
 
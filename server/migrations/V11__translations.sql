@@ -0,0 +1,7 @@
+create table translations(
+    locale varchar(35) not null,
+    key varchar(128) not null,
+    value varchar(2048) not null,
+    updated_at timestamp not null default now(),
+    primary key (locale, key)
+);
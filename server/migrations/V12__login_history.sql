@@ -0,0 +1,12 @@
+create table login_history(
+    id uuid not null primary key default gen_random_uuid(),
+    user_id uuid not null references users on delete cascade,
+    time timestamp not null default now(),
+    address inet,
+    user_agent varchar(512),
+    application uuid references applications on delete set null,
+    device uuid references user_devices on delete set null,
+    factors varchar(32)[] not null default '{}'
+);
+
+create index login_history_user_time on login_history(user_id, time desc);
@@ -0,0 +1 @@
+alter table consents add column declined varchar(64)[] not null default array[]::varchar(64)[];
@@ -0,0 +1,8 @@
+create table admin_sessions(
+    id uuid not null primary key default gen_random_uuid(),
+    session uuid not null references sessions on delete cascade,
+    token varchar(255) not null unique,
+    address inet,
+    creation_time timestamp not null default now(),
+    last_used timestamp not null default now()
+);
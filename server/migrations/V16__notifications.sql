@@ -0,0 +1,13 @@
+create type notification_severity as enum ('info', 'warning', 'critical');
+
+create table notifications(
+    id uuid not null primary key default gen_random_uuid(),
+    user_id uuid not null references users on delete cascade,
+    severity notification_severity not null,
+    title varchar(256) not null,
+    message text not null,
+    time timestamp not null default now(),
+    read_at timestamp
+);
+
+create index notifications_user_time on notifications(user_id, time desc);
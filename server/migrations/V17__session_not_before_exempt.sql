@@ -0,0 +1 @@
+alter table sessions add column not_before_exempt timestamptz;
@@ -0,0 +1,12 @@
+create type job_state as enum ('running', 'completed', 'failed');
+
+create table jobs(
+    id uuid not null primary key default gen_random_uuid(),
+    kind varchar(64) not null,
+    state job_state not null default 'running',
+    total bigint not null,
+    processed bigint not null default 0,
+    message text,
+    created_at timestamp not null default now(),
+    updated_at timestamp not null default now()
+);
@@ -0,0 +1 @@
+alter table user_devices alter column report_token drop not null;
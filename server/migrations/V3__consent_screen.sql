@@ -0,0 +1,17 @@
+create table scope_descriptions(
+    scope varchar(64) not null,
+    locale varchar(16) not null default 'en',
+    description varchar(256) not null,
+    primary key(scope, locale)
+);
+
+insert into scope_descriptions(scope, description) values
+    ('email', 'Read your email address'),
+    ('profile:read', 'Read your profile information'),
+    ('profile:write', 'Modify your profile');
+
+alter table applications
+    add column logo_uri varchar(256),
+    add column publisher varchar(64);
+
+alter table consents add column scopes varchar(64)[] not null default array[]::varchar(64)[];
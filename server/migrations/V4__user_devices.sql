@@ -0,0 +1,13 @@
+alter table sessions add column user_agent varchar(512);
+
+create table user_devices(
+    id uuid not null primary key default gen_random_uuid(),
+    user_id uuid not null references users on delete cascade,
+    user_agent varchar(512) not null,
+    address inet,
+    session uuid references sessions on delete set null,
+    report_token varchar(64) not null unique default encode(gen_random_bytes(32), 'hex'),
+    first_seen timestamp not null default now(),
+    last_seen timestamp not null default now(),
+    unique(user_id, user_agent, address)
+);
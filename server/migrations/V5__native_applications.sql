@@ -0,0 +1,5 @@
+alter type application_kind add value 'native';
+
+alter table authorization_codes
+    add column code_challenge varchar(128),
+    add column code_challenge_method varchar(8);
@@ -0,0 +1,5 @@
+create table feature_flags(
+    name varchar(64) primary key,
+    enabled boolean not null,
+    updated_at timestamp not null default now()
+);
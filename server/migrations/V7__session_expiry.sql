@@ -0,0 +1 @@
+alter table sessions add column last_used timestamp not null default now();
@@ -0,0 +1,5 @@
+alter table settings
+    add column maintenance boolean not null default false,
+    add column maintenance_message varchar(512);
+
+insert into settings(id) select true where not exists (select id from settings);
@@ -0,0 +1 @@
+alter table authorization_codes add column claims text;
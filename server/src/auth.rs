@@ -18,18 +18,23 @@ use crate::{
 
 pub const SESSION_COOKIE: &str = "session_token";
 pub const REFRESH_COOKIE: &str = "refresh_token";
+pub const ADMIN_SESSION_COOKIE: &str = "admin_session_token";
 
 pub const ISSUER: &str = "authentra";
+pub const ADMIN_AUDIENCE: &str = "admin";
 static EXPIRATION_DURATION: Duration = Duration::from_secs(2 * 60);
 
-static JWT_ALGO: Algorithm = Algorithm::HS256;
+pub static JWT_ALGO: Algorithm = Algorithm::HS256;
 
-static VALIDATION: Lazy<Validation> = Lazy::new(|| {
+pub fn validation(issuer: &str, leeway: u64) -> Validation {
     let mut validation = Validation::new(JWT_ALGO);
     validation.set_required_spec_claims(&["exp", "nbf", "iss", "sub"]);
-    validation.set_issuer(&[ISSUER]);
+    validation.set_issuer(&[issuer]);
+    validation.validate_exp = true;
+    validation.validate_nbf = true;
+    validation.leeway = leeway;
     validation
-});
+}
 
 pub fn jwt_header() -> Header {
     Header::new(JWT_ALGO)
@@ -41,15 +46,32 @@ static BEARER_AUTH_REGEX: Lazy<Regex> =
 pub struct AuthState {
     encoding: EncodingKey,
     decoding: DecodingKey,
+    issuer: String,
+    validation: Validation,
+    admin_validation: Validation,
 }
 
 impl AuthState {
-    pub fn new(secret: &str) -> Self {
+    pub fn new(secret: &str, issuer: String, leeway: u64) -> Self {
+        let mut admin_validation = validation(&issuer, leeway);
+        admin_validation.set_audience(&[ADMIN_AUDIENCE]);
         Self {
             encoding: EncodingKey::from_secret(secret.as_bytes()),
             decoding: DecodingKey::from_secret(secret.as_bytes()),
+            validation: validation(&issuer, leeway),
+            admin_validation,
+            issuer,
         }
     }
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+    pub fn validation(&self) -> &Validation {
+        &self.validation
+    }
+    pub fn admin_validation(&self) -> &Validation {
+        &self.admin_validation
+    }
     pub fn encoding(&self) -> &EncodingKey {
         &self.encoding
     }
@@ -57,7 +79,7 @@ impl AuthState {
         &self.decoding
     }
 }
-#[derive(Debug, Display, Deserialize, Serialize, ToSql, FromSql, PartialEq, Eq)]
+#[derive(Debug, Clone, Display, Deserialize, Serialize, ToSql, FromSql, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 #[postgres(name = "user_roles")]
 pub enum UserRole {
@@ -84,13 +106,13 @@ pub struct BaseClaims<T> {
 }
 
 impl<T> BaseClaims<T> {
-    pub fn new(user: Uuid, session: T) -> Self {
+    pub fn new(issuer: &str, user: Uuid, session: T) -> Self {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Failed to get time since epoch")
             .as_secs();
         Self {
-            iss: ISSUER.into(),
+            iss: issuer.into(),
             exp: (SystemTime::now() + EXPIRATION_DURATION)
                 .duration_since(UNIX_EPOCH)
                 .expect("Failed to get time since epoch")
@@ -115,6 +137,7 @@ pub struct OAuthClaims {
 
 impl OAuthClaims {
     pub fn new(
+        issuer: &str,
         user: Uuid,
         session: String,
         application: String,
@@ -122,7 +145,7 @@ impl OAuthClaims {
         authentra: AuthentraClaims,
     ) -> Self {
         Self {
-            base: BaseClaims::new(user, session),
+            base: BaseClaims::new(issuer, user, session),
             azp: application,
             scope,
             authentra,
@@ -134,6 +157,8 @@ impl OAuthClaims {
 pub struct Claims {
     #[serde(flatten)]
     pub base: BaseClaims<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
     pub authentra: AuthentraClaims,
 }
 
@@ -143,12 +168,22 @@ pub struct AuthentraClaims {
 }
 
 impl Claims {
-    pub fn new(user: Uuid, session: Uuid, authentra: AuthentraClaims) -> Self {
+    pub fn new(issuer: &str, user: Uuid, session: Uuid, authentra: AuthentraClaims) -> Self {
         Self {
-            base: BaseClaims::new(user, session),
+            base: BaseClaims::new(issuer, user, session),
+            aud: None,
             authentra,
         }
     }
+
+    /// Claims for an elevated admin session. They carry their own audience
+    /// and are only accepted through [`AuthState::admin_validation`].
+    pub fn admin(issuer: &str, user: Uuid, session: Uuid, authentra: AuthentraClaims) -> Self {
+        Self {
+            aud: Some(ADMIN_AUDIENCE.into()),
+            ..Self::new(issuer, user, session, authentra)
+        }
+    }
 }
 
 #[derive(Debug, Display)]
@@ -161,6 +196,8 @@ pub enum AuthError {
     InvalidHeader,
     #[display("Invalid session")]
     InvalidSession,
+    #[display("Session revoked")]
+    SessionRevoked,
     #[display("Invalid credentials")]
     InvalidCredentials,
     #[display("Claims missing in session info")]
@@ -171,6 +208,7 @@ pub struct SessionInfo {
     pub id: Uuid,
     pub user: Uuid,
     pub claims: Option<Claims>,
+    pub elevated: bool,
 }
 
 impl SessionInfo {
@@ -180,16 +218,19 @@ impl SessionInfo {
     pub fn check_developer(&self) -> AppResult<()> {
         self.check_role(UserRole::Developer)
     }
+    fn grants(&self, claims: &Claims, role: &UserRole) -> bool {
+        let roles = &claims.authentra.roles;
+        if roles.contains(&UserRole::Admin) && self.elevated {
+            return true;
+        }
+        *role != UserRole::Admin && roles.contains(role)
+    }
     #[inline(always)]
     pub fn has_role(&self, role: UserRole) -> bool {
-        if let Some(claims) = &self.claims {
-            if claims.authentra.roles.contains(&role)
-                || claims.authentra.roles.contains(&UserRole::Admin)
-            {
-                return true;
-            }
+        match &self.claims {
+            Some(claims) => self.grants(claims, &role),
+            None => false,
         }
-        false
     }
     #[inline(always)]
     #[instrument(skip_all, fields(roles,required = %role))]
@@ -197,9 +238,7 @@ impl SessionInfo {
         let current = Span::current();
         if let Some(claims) = &self.claims {
             current.record("roles", format!("{:?}", claims.authentra.roles));
-            if claims.authentra.roles.contains(&role)
-                || claims.authentra.roles.contains(&UserRole::Admin)
-            {
+            if self.grants(claims, &role) {
                 return Ok(());
             }
         }
@@ -238,16 +277,27 @@ async fn cookie_auth(parts: &Parts, state: &AppState) -> Result<SessionInfo, Err
     let cookies = CookieJar::from_headers(&parts.headers);
     let Some(session) = cookies.get(SESSION_COOKIE) else { return Err(AuthError::MissingCookie.into()) };
     let value = session.value();
+    let lifetimes = &state.config().session;
     let conn = state.conn().await?;
     let stmt = conn
-        .prepare_cached("select id,user_id from sessions where token = $1")
+        .prepare_cached("update sessions set last_used = now() where token = $1 and creation_time > now() - make_interval(secs => $2) and last_used > now() - make_interval(secs => $3) returning id,user_id")
+        .await?;
+    let row = conn
+        .query_opt(
+            &stmt,
+            &[
+                &value,
+                &(lifetimes.lifetime as f64),
+                &(lifetimes.idle as f64),
+            ],
+        )
         .await?;
-    let row = conn.query_opt(&stmt, &[&value]).await?;
     match row {
         Some(row) => Ok(SessionInfo {
             id: row.get("id"),
             user: row.get("user_id"),
             claims: None,
+            elevated: false,
         }),
         None => Err(AuthError::InvalidSession.into()),
     }
@@ -262,11 +312,35 @@ async fn api_auth(parts: &Parts, state: &AppState) -> Result<SessionInfo, Error>
     let Some(capture) = BEARER_AUTH_REGEX.captures(header) else { return Err(AuthError::InvalidHeader.into()) };
     let Some(m) = capture.get(1) else { return Err(AuthError::InvalidHeader.into()) };
     let token = m.as_str();
-    let token: TokenData<Claims> =
-        jsonwebtoken::decode(token, &state.auth().decoding(), &VALIDATION)?;
+    let auth = state.auth();
+    let (token, admin): (TokenData<Claims>, bool) =
+        match jsonwebtoken::decode(token, auth.decoding(), auth.admin_validation()) {
+            Ok(admin) => (admin, true),
+            Err(_) => (
+                jsonwebtoken::decode(token, auth.decoding(), auth.validation())?,
+                false,
+            ),
+        };
+    if !admin && token.claims.aud.is_some() {
+        return Err(AuthError::InvalidHeader.into());
+    }
+    let sessions = state.sessions();
+    let session = match sessions.cached(&token.claims.base.sid) {
+        Some(session) => session,
+        None => {
+            let conn = state.conn().await?;
+            sessions
+                .state(&conn, &token.claims.base.sid, &token.claims.base.sub)
+                .await?
+        }
+    };
+    if !session.accepts(token.claims.base.iat) {
+        return Err(AuthError::SessionRevoked.into());
+    }
     Ok(SessionInfo {
         id: token.claims.base.sid,
         user: token.claims.base.sub,
+        elevated: !state.config().admin.session || admin,
         claims: Some(token.claims),
     })
 }
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use deadpool_postgres::GenericClient;
+use futures::StreamExt;
+use tokio_postgres::{AsyncMessage, NoTls};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{AppResult, AppState};
+
+const CHANNEL: &str = "authentra_cache";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Cache invalidations that have to reach every replica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheEvent {
+    Session(Uuid),
+    Sessions,
+    All,
+}
+
+impl CacheEvent {
+    fn payload(&self) -> String {
+        match self {
+            CacheEvent::Session(id) => format!("session:{id}"),
+            CacheEvent::Sessions => "sessions".to_string(),
+            CacheEvent::All => "all".to_string(),
+        }
+    }
+
+    fn from_payload(payload: &str) -> Option<Self> {
+        match payload {
+            "sessions" => Some(CacheEvent::Sessions),
+            "all" => Some(CacheEvent::All),
+            _ => payload
+                .strip_prefix("session:")
+                .and_then(|id| id.parse().ok())
+                .map(CacheEvent::Session),
+        }
+    }
+}
+
+#[instrument(skip_all, name = "broadcast", fields(event = ?event))]
+pub async fn send(client: &impl GenericClient, event: CacheEvent) -> AppResult<()> {
+    let stmt = client.prepare_cached("select pg_notify($1, $2)").await?;
+    client.execute(&stmt, &[&CHANNEL, &event.payload()]).await?;
+    Ok(())
+}
+
+pub async fn reload_settings(state: &AppState) -> AppResult<()> {
+    let conn = state.conn().await?;
+    state.features().reload(&conn).await?;
+    state.maintenance().reload(&conn).await
+}
+
+fn apply(state: &AppState, event: CacheEvent) {
+    if let CacheEvent::Session(id) = event {
+        state.sessions().invalidate(&id);
+        return;
+    }
+    state.sessions().clear();
+    if event == CacheEvent::All {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = reload_settings(&state).await {
+                tracing::warn!("Failed to reload settings after cache event: {err}");
+            }
+        });
+    }
+}
+
+pub async fn listen(config: tokio_postgres::Config, state: AppState) {
+    loop {
+        if let Err(err) = subscribe(&config, &state).await {
+            tracing::warn!("Cache invalidation listener failed: {err}");
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe(
+    config: &tokio_postgres::Config,
+    state: &AppState,
+) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = config.connect(NoTls).await?;
+    let handler = state.clone();
+    let driver = tokio::spawn(async move {
+        let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if let AsyncMessage::Notification(notification) = message? {
+                match CacheEvent::from_payload(notification.payload()) {
+                    Some(event) => apply(&handler, event),
+                    None => tracing::warn!(payload = notification.payload(), "Unknown cache event"),
+                }
+            }
+        }
+        Ok::<_, tokio_postgres::Error>(())
+    });
+    client.batch_execute(&format!("listen {CHANNEL}")).await?;
+    // Events sent while the listener was disconnected are lost.
+    apply(state, CacheEvent::Sessions);
+    driver.await.unwrap_or(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::CacheEvent;
+
+    #[test]
+    fn payload_round_trip() {
+        for event in [
+            CacheEvent::Session(Uuid::new_v4()),
+            CacheEvent::Sessions,
+            CacheEvent::All,
+        ] {
+            assert_eq!(CacheEvent::from_payload(&event.payload()), Some(event));
+        }
+        assert_eq!(CacheEvent::from_payload("session:nope"), None);
+    }
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use crate::config::ChaosConfiguration;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Chaos {
+    enabled: bool,
+    latency: Duration,
+    errors: f64,
+}
+
+impl Chaos {
+    pub fn new(config: &ChaosConfiguration) -> Self {
+        Self {
+            enabled: config.enabled && cfg!(debug_assertions),
+            latency: Duration::from_millis(config.latency),
+            errors: config.errors,
+        }
+    }
+
+    pub async fn inject(&self, target: &'static str) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency.mul_f64(rand::random())).await;
+        }
+        let fail = rand::random::<f64>() < self.errors;
+        if fail {
+            tracing::warn!(target, "Injected fault");
+        }
+        fail
+    }
+}
@@ -0,0 +1,146 @@
+use std::io::Read;
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::State,
+    http::{
+        header::{CONTENT_ENCODING, CONTENT_LENGTH},
+        Request, StatusCode,
+    },
+    middleware::Next,
+    response::Response,
+};
+use flate2::read::GzDecoder;
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+use crate::{error::ApiError, AppResult};
+
+pub fn layer(threshold: u16) -> CompressionLayer<impl Predicate + Clone> {
+    let predicate = SizeAbove::new(threshold)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::const_new("text/event-stream"));
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(predicate)
+}
+
+/// Decompressed body limits, mirroring the per-route `DefaultBodyLimit`
+/// overrides so gzip bodies are capped at the limit of the route they target.
+#[derive(Clone)]
+pub struct BodyLimits {
+    default: usize,
+    routes: Vec<(&'static str, usize)>,
+}
+
+impl BodyLimits {
+    pub fn new(default: usize) -> Self {
+        Self {
+            default,
+            routes: Vec::new(),
+        }
+    }
+
+    pub fn route(mut self, path: &'static str, limit: usize) -> Self {
+        self.routes.push((path, limit));
+        self
+    }
+
+    fn for_path(&self, path: &str) -> usize {
+        self.routes
+            .iter()
+            .find(|(route, _)| *route == path)
+            .map(|(_, limit)| *limit)
+            .unwrap_or(self.default)
+    }
+}
+
+fn too_large(limit: usize) -> ApiError {
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Request body exceeds {limit} bytes"),
+    )
+}
+
+fn inflate(bytes: &[u8], limit: usize) -> AppResult<Vec<u8>> {
+    let mut decoded = Vec::new();
+    GzDecoder::new(bytes)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "Invalid gzip body"))?;
+    if decoded.len() > limit {
+        return Err(too_large(limit).into());
+    }
+    Ok(decoded)
+}
+
+pub async fn decompress(
+    State(limits): State<BodyLimits>,
+    mut request: Request<Body>,
+    next: Next<Body>,
+) -> AppResult<Response> {
+    let limit = limits.for_path(request.uri().path());
+    let encoding = request
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase());
+    match encoding.as_deref() {
+        None | Some("identity") => Ok(next.run(request).await),
+        Some("gzip") => {
+            let mut body = std::mem::take(request.body_mut());
+            let mut compressed = Vec::new();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk
+                    .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "Failed to read body"))?;
+                if compressed.len() + chunk.len() > limit {
+                    return Err(too_large(limit).into());
+                }
+                compressed.extend_from_slice(&chunk);
+            }
+            *request.body_mut() = Body::from(inflate(&compressed, limit)?);
+            request.headers_mut().remove(CONTENT_ENCODING);
+            request.headers_mut().remove(CONTENT_LENGTH);
+            Ok(next.run(request).await)
+        }
+        Some(encoding) => Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("Unsupported content encoding '{encoding}'"),
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::{inflate, BodyLimits};
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn inflates_within_limit() {
+        let body = br#"{"name":"authentra"}"#;
+        assert_eq!(inflate(&gzip(body), 64).unwrap(), body);
+        assert!(inflate(&gzip(&[0; 128]), 64).is_err());
+        assert!(inflate(b"not gzip", 64).is_err());
+    }
+
+    #[test]
+    fn route_limits_override_default() {
+        let limits = BodyLimits::new(64).route("/api/v1/me/avatar", 1024);
+        assert_eq!(limits.for_path("/api/v1/me/avatar"), 1024);
+        assert_eq!(limits.for_path("/api/v1/me"), 64);
+    }
+}
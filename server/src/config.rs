@@ -1,14 +1,170 @@
-use std::net::{Ipv6Addr, SocketAddr};
+use std::{
+    collections::HashMap,
+    net::{Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 use config::{Config, ConfigError, Environment};
 use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    auth::ISSUER,
+    media::MediaBackend,
+    utils::{
+        breach::{BreachProvider, FailMode},
+        network::Cidr,
+    },
+};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthentraConfiguration {
     pub listen: ListenConfiguration,
     pub postgres: deadpool_postgres::Config,
+    pub statement_timeout: u64,
     pub secret: String,
+    pub external_url: Option<String>,
+    pub base: String,
     pub allowed_origins: Vec<String>,
+    pub password: PasswordConfiguration,
+    pub session: SessionConfiguration,
+    pub settings: SettingsConfiguration,
+    pub admin: AdminConfiguration,
+    pub page: PageConfiguration,
+    pub media: MediaConfiguration,
+    pub body: BodyConfiguration,
+    pub compression: CompressionConfiguration,
+    pub timeout: TimeoutConfiguration,
+    pub audit: AuditConfiguration,
+    pub shed: ShedConfiguration,
+    pub chaos: ChaosConfiguration,
+    #[serde(default)]
+    pub trusted_proxies: Vec<Cidr>,
+    #[serde(default)]
+    pub admin_allow: Vec<Cidr>,
+    #[serde(default)]
+    pub admin_deny: Vec<Cidr>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionConfiguration {
+    pub lifetime: u64,
+    pub idle: u64,
+    pub history: u64,
+    pub leeway: u64,
+    pub cache: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettingsConfiguration {
+    pub cache: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdminConfiguration {
+    pub session: bool,
+    pub lifetime: u64,
+    pub idle: u64,
+    pub bind: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageConfiguration {
+    pub default: u16,
+    pub max: u16,
+    #[serde(default)]
+    pub endpoints: HashMap<String, PageOverride>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageOverride {
+    pub default: Option<u16>,
+    pub max: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PageLimits {
+    pub default: u16,
+    pub max: u16,
+}
+
+impl PageConfiguration {
+    pub fn limits(&self, endpoint: &str) -> PageLimits {
+        let endpoint = self.endpoints.get(endpoint);
+        let max = endpoint.and_then(|e| e.max).unwrap_or(self.max);
+        let default = endpoint.and_then(|e| e.default).unwrap_or(self.default);
+        PageLimits {
+            default: default.min(max),
+            max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MediaConfiguration {
+    pub storage: MediaBackend,
+    pub path: PathBuf,
+    pub endpoint: Option<String>,
+    pub bucket: Option<String>,
+    pub region: String,
+    pub access: Option<String>,
+    pub secret: Option<String>,
+    pub size: usize,
+    pub lifetime: u64,
+    pub gravatar: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BodyConfiguration {
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompressionConfiguration {
+    pub threshold: u16,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChaosConfiguration {
+    pub enabled: bool,
+    pub latency: u64,
+    pub errors: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShedConfiguration {
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuditConfiguration {
+    pub reads: bool,
+    pub sample: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeoutConfiguration {
+    pub default: u64,
+    #[serde(default)]
+    pub endpoints: HashMap<String, u64>,
+}
+
+impl TimeoutConfiguration {
+    pub fn budget(&self, endpoint: &str) -> Duration {
+        Duration::from_millis(*self.endpoints.get(endpoint).unwrap_or(&self.default))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PasswordConfiguration {
+    pub length: usize,
+    pub classes: usize,
+    pub revoke: bool,
+    pub breach: BreachProvider,
+    pub list: Option<PathBuf>,
+    pub timeout: u64,
+    pub fail: FailMode,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -36,12 +192,305 @@ impl AuthentraConfiguration {
                     .ignore_empty(true)
                     .try_parsing(true)
                     .with_list_parse_key("allowed_origins")
+                    .with_list_parse_key("trusted_proxies")
+                    .with_list_parse_key("admin_allow")
+                    .with_list_parse_key("admin_deny")
                     .list_separator(" "),
             )
             .set_default("listen.http", default_listen.http.to_string())?
             .set_default("listen.metrics", default_listen.metrics.to_string())?
             .set_default("postgres.port", 5432)?
+            .set_default("statement_timeout", 30_000)?
+            .set_default("base", "")?
+            .set_default("password.length", 8)?
+            .set_default("password.classes", 2)?
+            .set_default("password.revoke", true)?
+            .set_default("password.breach", "none")?
+            .set_default("password.timeout", 2_000)?
+            .set_default("password.fail", "open")?
+            .set_default("session.lifetime", 14 * 24 * 60 * 60)?
+            .set_default("session.idle", 7 * 24 * 60 * 60)?
+            .set_default("session.history", 90 * 24 * 60 * 60)?
+            .set_default("session.leeway", 30)?
+            .set_default("session.cache", 5)?
+            .set_default("settings.cache", 10)?
+            .set_default("admin.session", false)?
+            .set_default("admin.lifetime", 60 * 60)?
+            .set_default("admin.idle", 15 * 60)?
+            .set_default("admin.bind", false)?
+            .set_default("page.default", 25)?
+            .set_default("page.max", 100)?
+            .set_default("media.storage", "local")?
+            .set_default("media.path", "media")?
+            .set_default("media.region", "us-east-1")?
+            .set_default("media.size", 2 * 1024 * 1024)?
+            .set_default("media.lifetime", 60 * 60)?
+            .set_default("media.gravatar", false)?
+            .set_default("body.limit", 64 * 1024)?
+            .set_default("compression.threshold", 1024)?
+            .set_default("timeout.default", 10_000)?
+            .set_default("audit.reads", false)?
+            .set_default("audit.sample", 1.0)?
+            .set_default("shed.depth", 0)?
+            .set_default("chaos.enabled", false)?
+            .set_default("chaos.latency", 0)?
+            .set_default("chaos.errors", 0.0)?
             .build()?;
         loaded.try_deserialize()
     }
 }
+
+const REDACTED: &str = "<redacted>";
+
+impl AuthentraConfiguration {
+    pub fn issuer(&self) -> String {
+        match &self.external_url {
+            Some(_) => self.public_url(),
+            None => ISSUER.to_string(),
+        }
+    }
+
+    pub fn public_url(&self) -> String {
+        let origin = self
+            .external_url
+            .as_deref()
+            .map_or("", |url| url.trim_end_matches('/'));
+        format!("{origin}{}", self.base)
+    }
+
+    pub fn cookie_path(&self) -> &str {
+        if self.base.is_empty() {
+            "/"
+        } else {
+            &self.base
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.listen.http.port() == 0 {
+            errors.push("listen.http: port must be between 1 and 65535".to_string());
+        }
+        if self.listen.metrics.port() == 0 {
+            errors.push("listen.metrics: port must be between 1 and 65535".to_string());
+        }
+        if self.listen.http == self.listen.metrics {
+            errors.push("listen.metrics: must differ from listen.http".to_string());
+        }
+        if let Some(external_url) = &self.external_url {
+            match Url::parse(external_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+                _ => errors.push(format!("external_url: '{external_url}' is not a valid url")),
+            }
+        }
+        if !self.base.is_empty()
+            && (!self.base.starts_with('/')
+                || self.base.ends_with('/')
+                || self.base.contains(['?', '#', ' ']))
+        {
+            errors.push(format!(
+                "base: '{}' must start with '/' and not end with '/'",
+                self.base
+            ));
+        }
+        if self.secret.len() < 8 {
+            errors.push("secret: must be at least 8 characters long".to_string());
+        }
+        for origin in &self.allowed_origins {
+            match Url::parse(origin) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+                _ => errors.push(format!("allowed_origins: '{origin}' is not a valid origin")),
+            }
+        }
+        if self.statement_timeout == 0 {
+            errors.push("statement_timeout: must be greater than 0".to_string());
+        }
+        if self.password.length == 0 {
+            errors.push("password.length: must be greater than 0".to_string());
+        }
+        if self.password.classes > 4 {
+            errors.push("password.classes: must be between 0 and 4".to_string());
+        }
+        if self.password.breach == BreachProvider::Local && self.password.list.is_none() {
+            errors.push("password.list: required when password.breach is local".to_string());
+        }
+        if self.password.timeout == 0 {
+            errors.push("password.timeout: must be greater than 0".to_string());
+        }
+        if self.session.lifetime == 0 {
+            errors.push("session.lifetime: must be greater than 0".to_string());
+        }
+        if self.session.idle == 0 || self.session.idle > self.session.lifetime {
+            errors.push("session.idle: must be between 1 and session.lifetime".to_string());
+        }
+        if self.session.leeway > 300 {
+            errors.push("session.leeway: must not exceed 300 seconds".to_string());
+        }
+        if self.session.cache > 300 {
+            errors.push("session.cache: must not exceed 300 seconds".to_string());
+        }
+        if self.settings.cache == 0 || self.settings.cache > 300 {
+            errors.push("settings.cache: must be between 1 and 300 seconds".to_string());
+        }
+        if self.admin.lifetime == 0 || self.admin.lifetime > self.session.lifetime {
+            errors.push("admin.lifetime: must be between 1 and session.lifetime".to_string());
+        }
+        if self.admin.idle == 0 || self.admin.idle > self.admin.lifetime {
+            errors.push("admin.idle: must be between 1 and admin.lifetime".to_string());
+        }
+        if self.session.history == 0 {
+            errors.push("session.history: must be greater than 0".to_string());
+        }
+        if self.page.max == 0 {
+            errors.push("page.max: must be greater than 0".to_string());
+        }
+        if self.page.default > self.page.max {
+            errors.push("page.default: must not be greater than page.max".to_string());
+        }
+        for (endpoint, limits) in &self.page.endpoints {
+            if limits.max == Some(0) {
+                errors.push(format!(
+                    "page.endpoints.{endpoint}.max: must be greater than 0"
+                ));
+            }
+        }
+        if self.media.storage == MediaBackend::S3 {
+            for (name, value) in [
+                ("endpoint", &self.media.endpoint),
+                ("bucket", &self.media.bucket),
+                ("access", &self.media.access),
+                ("secret", &self.media.secret),
+            ] {
+                if value.is_none() {
+                    errors.push(format!("media.{name}: required when media.storage is s3"));
+                }
+            }
+        }
+        if self.media.size == 0 {
+            errors.push("media.size: must be greater than 0".to_string());
+        }
+        if self.media.lifetime == 0 {
+            errors.push("media.lifetime: must be greater than 0".to_string());
+        }
+        if self.body.limit == 0 {
+            errors.push("body.limit: must be greater than 0".to_string());
+        }
+        if self.timeout.default == 0 {
+            errors.push("timeout.default: must be greater than 0".to_string());
+        }
+        for (endpoint, budget) in &self.timeout.endpoints {
+            if *budget == 0 {
+                errors.push(format!(
+                    "timeout.endpoints.{endpoint}: must be greater than 0"
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.audit.sample) {
+            errors.push("audit.sample: must be between 0 and 1".to_string());
+        }
+        if self.chaos.enabled && !cfg!(debug_assertions) {
+            errors.push("chaos.enabled: only available in debug builds".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.chaos.errors) {
+            errors.push("chaos.errors: must be between 0 and 1".to_string());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let postgres = &self.postgres;
+        let cidrs = |list: &[Cidr]| {
+            list.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut lines = vec![
+            format!("listen.http = {}", self.listen.http),
+            format!("listen.metrics = {}", self.listen.metrics),
+            format!("postgres.host = {:?}", postgres.host),
+            format!("postgres.port = {:?}", postgres.port),
+            format!("postgres.dbname = {:?}", postgres.dbname),
+            format!("postgres.user = {:?}", postgres.user),
+            format!(
+                "postgres.password = {}",
+                postgres.password.as_ref().map_or("<unset>", |_| REDACTED)
+            ),
+            format!("statement_timeout = {}ms", self.statement_timeout),
+            format!("secret = {REDACTED}"),
+            format!("external_url = {:?}", self.external_url),
+            format!("issuer = {}", self.issuer()),
+            format!("base = {:?}", self.base),
+            format!("allowed_origins = {}", self.allowed_origins.join(" ")),
+            format!("password.length = {}", self.password.length),
+            format!("password.classes = {}", self.password.classes),
+            format!("password.revoke = {}", self.password.revoke),
+            format!("password.breach = {:?}", self.password.breach),
+            format!("password.list = {:?}", self.password.list),
+            format!("password.timeout = {}ms", self.password.timeout),
+            format!("password.fail = {:?}", self.password.fail),
+            format!("session.lifetime = {}s", self.session.lifetime),
+            format!("session.idle = {}s", self.session.idle),
+            format!("session.history = {}s", self.session.history),
+            format!("session.leeway = {}s", self.session.leeway),
+            format!("session.cache = {}s", self.session.cache),
+            format!("settings.cache = {}s", self.settings.cache),
+            format!("admin.session = {}", self.admin.session),
+            format!("admin.lifetime = {}s", self.admin.lifetime),
+            format!("admin.idle = {}s", self.admin.idle),
+            format!("admin.bind = {}", self.admin.bind),
+            format!("page.default = {}", self.page.default),
+            format!("page.max = {}", self.page.max),
+        ];
+        let mut endpoints: Vec<_> = self.page.endpoints.iter().collect();
+        endpoints.sort_by(|a, b| a.0.cmp(b.0));
+        for (endpoint, limits) in endpoints {
+            lines.push(format!(
+                "page.endpoints.{endpoint} = default {:?}, max {:?}",
+                limits.default, limits.max
+            ));
+        }
+        lines.push(format!("media.storage = {:?}", self.media.storage));
+        match self.media.storage {
+            MediaBackend::Local => {
+                lines.push(format!("media.path = {}", self.media.path.display()));
+            }
+            MediaBackend::S3 => {
+                lines.push(format!("media.endpoint = {:?}", self.media.endpoint));
+                lines.push(format!("media.bucket = {:?}", self.media.bucket));
+                lines.push(format!("media.region = {}", self.media.region));
+            }
+        }
+        lines.push(format!("media.size = {}", self.media.size));
+        lines.push(format!("media.lifetime = {}s", self.media.lifetime));
+        lines.push(format!("media.gravatar = {}", self.media.gravatar));
+        lines.push(format!("body.limit = {}", self.body.limit));
+        lines.push(format!(
+            "compression.threshold = {}",
+            self.compression.threshold
+        ));
+        lines.push(format!("timeout.default = {}ms", self.timeout.default));
+        let mut budgets: Vec<_> = self.timeout.endpoints.iter().collect();
+        budgets.sort_by(|a, b| a.0.cmp(b.0));
+        for (endpoint, budget) in budgets {
+            lines.push(format!("timeout.endpoints.{endpoint} = {budget}ms"));
+        }
+        lines.push(format!("audit.reads = {}", self.audit.reads));
+        lines.push(format!("audit.sample = {}", self.audit.sample));
+        lines.push(format!("shed.depth = {}", self.shed.depth));
+        lines.push(format!("chaos.enabled = {}", self.chaos.enabled));
+        lines.push(format!("chaos.latency = {}ms", self.chaos.latency));
+        lines.push(format!("chaos.errors = {}", self.chaos.errors));
+        lines.push(format!(
+            "trusted_proxies = {}",
+            cidrs(&self.trusted_proxies)
+        ));
+        lines.push(format!("admin_allow = {}", cidrs(&self.admin_allow)));
+        lines.push(format!("admin_deny = {}", cidrs(&self.admin_deny)));
+        lines.join("\n")
+    }
+}
@@ -5,7 +5,7 @@ use std::{
 
 use argon2::password_hash::Error as ArgonError;
 use axum::{
-    extract::rejection::{JsonRejection, QueryRejection},
+    extract::rejection::{BytesRejection, JsonRejection, QueryRejection},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
@@ -15,6 +15,7 @@ use derive_more::{Display, Error, From};
 use jsonwebtoken::errors::{Error as JwtError, ErrorKind as JwtErrorKind};
 use serde::Serialize;
 use tokio::task::JoinError;
+use tokio_postgres::error::SqlState;
 use tracing_error::SpanTrace;
 
 use crate::{auth::AuthError, routes::oauth::NewError};
@@ -121,12 +122,16 @@ pub enum ErrorKind {
     HeaderNotAscii { header_name: &'static str },
     #[display("Tokio failed to join task")]
     TokioJoin(JoinError),
+    #[display("IO: {}", _0)]
+    Io(std::io::Error),
     #[display("OAuth: {}", _0)]
     OAuth(#[error(not(source))] NewError),
     #[display("Json: {}", _0)]
     Json(JsonRejection),
     #[display("Query: {}", _0)]
     Query(QueryRejection),
+    #[display("Body: {}", _0)]
+    Body(BytesRejection),
 }
 
 impl ErrorKind {
@@ -173,6 +178,10 @@ impl<T: Into<ErrorKind>> IntoError for T {
     }
 }
 
+fn is_statement_timeout(err: &tokio_postgres::Error) -> bool {
+    err.code() == Some(&SqlState::QUERY_CANCELED)
+}
+
 fn argon_error(err: &ArgonError) -> ResponseError {
     match err {
         ArgonError::Password => (StatusCode::UNAUTHORIZED, "Invalid password").into(),
@@ -265,9 +274,15 @@ struct JsonErrorResponse {
 impl ErrorKind {
     fn response(&self) -> ResponseError {
         match self {
-            ErrorKind::PoolError(_) | ErrorKind::PostgresError(_) | ErrorKind::TokioJoin(_) => {
-                StatusCode::INTERNAL_SERVER_ERROR.into()
-            }
+            ErrorKind::PostgresError(err) if is_statement_timeout(err) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Database statement timeout",
+            )
+                .into(),
+            ErrorKind::PoolError(_)
+            | ErrorKind::PostgresError(_)
+            | ErrorKind::TokioJoin(_)
+            | ErrorKind::Io(_) => StatusCode::INTERNAL_SERVER_ERROR.into(),
             ErrorKind::Status(status) => status.clone().into(),
             ErrorKind::Jwt(err) => jwt_response(err),
             ErrorKind::Argon(err) => argon_error(err),
@@ -281,6 +296,7 @@ impl ErrorKind {
                 match auth {
                     AuthError::MissingCookie => (status, "Authentication cookie missing").into(),
                     AuthError::InvalidSession => (status, "Invalid session").into(),
+                    AuthError::SessionRevoked => (status, "Session revoked").into(),
                     AuthError::MissingHeader => (status, "Authorization header missing").into(),
                     AuthError::InvalidHeader => (status, "Authorization header is invalid").into(),
                     AuthError::InvalidCredentials => (status, "Invalid credentials").into(),
@@ -291,6 +307,7 @@ impl ErrorKind {
             ErrorKind::OAuth(err) => (err.kind.status(), "OAuth Error").into(),
             ErrorKind::Json(json) => (json.status(), json.body_text()).into(),
             ErrorKind::Query(query) => (query.status(), query.body_text()).into(),
+            ErrorKind::Body(body) => (body.status(), body.body_text()).into(),
         }
     }
 }
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, sync::RwLock, time::Duration};
+
+use deadpool_postgres::GenericClient;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{AppResult, AppState};
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    #[display("native_applications")]
+    NativeApplications,
+    #[display("device_alerts")]
+    DeviceAlerts,
+}
+
+impl Feature {
+    pub const fn values() -> [Feature; 2] {
+        [Feature::NativeApplications, Feature::DeviceAlerts]
+    }
+
+    pub const fn default_enabled(&self) -> bool {
+        match self {
+            Feature::NativeApplications => true,
+            Feature::DeviceAlerts => true,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::values()
+            .into_iter()
+            .find(|feature| feature.to_string() == name)
+    }
+}
+
+pub struct FeatureFlags(RwLock<HashMap<Feature, bool>>);
+
+impl FeatureFlags {
+    #[instrument(skip_all, name = "load_feature_flags")]
+    pub async fn load(client: &impl GenericClient) -> AppResult<Self> {
+        let flags = Self(RwLock::new(HashMap::new()));
+        flags.reload(client).await?;
+        Ok(flags)
+    }
+
+    pub async fn reload(&self, client: &impl GenericClient) -> AppResult<()> {
+        let stmt = client
+            .prepare_cached("select name,enabled from feature_flags")
+            .await?;
+        let rows = client.query(&stmt, &[]).await?;
+        let loaded: HashMap<Feature, bool> = rows
+            .into_iter()
+            .filter_map(|row| {
+                let name: String = row.get("name");
+                Feature::from_name(&name).map(|feature| (feature, row.get("enabled")))
+            })
+            .collect();
+        *self.0.write().expect("Feature flag lock poisoned") = loaded;
+        Ok(())
+    }
+
+    pub fn enabled(&self, feature: Feature) -> bool {
+        self.0
+            .read()
+            .expect("Feature flag lock poisoned")
+            .get(&feature)
+            .copied()
+            .unwrap_or(feature.default_enabled())
+    }
+
+    pub fn all(&self) -> Vec<(Feature, bool)> {
+        Feature::values()
+            .into_iter()
+            .map(|feature| (feature, self.enabled(feature)))
+            .collect()
+    }
+
+    pub async fn set(
+        &self,
+        client: &impl GenericClient,
+        feature: Feature,
+        enabled: bool,
+    ) -> AppResult<()> {
+        let stmt = client
+            .prepare_cached("insert into feature_flags(name,enabled) values($1,$2) on conflict (name) do update set enabled = excluded.enabled, updated_at = now()")
+            .await?;
+        client
+            .execute(&stmt, &[&feature.to_string(), &enabled])
+            .await?;
+        self.0
+            .write()
+            .expect("Feature flag lock poisoned")
+            .insert(feature, enabled);
+        Ok(())
+    }
+}
+
+/// Re-reads the flags periodically so toggles made through another replica
+/// take effect here as well.
+pub async fn refresh(state: AppState, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let reloaded = match state.conn().await {
+            Ok(conn) => state.features().reload(&conn).await,
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = reloaded {
+            tracing::warn!("Failed to refresh feature flags: {err}");
+        }
+    }
+}
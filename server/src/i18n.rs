@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use deadpool_postgres::GenericClient;
+use tracing::instrument;
+
+use crate::AppResult;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+const EN: &[(&str, &str)] = &[
+    ("auth.login.title", "Login"),
+    ("auth.login.user", "Username or Email"),
+    ("auth.login.password", "Password"),
+    ("auth.login.submit", "Login"),
+    ("auth.register.title", "Register"),
+    ("auth.register.submit", "Register"),
+    ("auth.error.invalid_credentials", "Invalid credentials"),
+    (
+        "auth.error.maintenance",
+        "Authentra is currently in maintenance mode",
+    ),
+    ("oauth.authorize.title", "Authorize Application"),
+    ("oauth.authorize.submit", "Authorize"),
+    ("oauth.authorize.cancel", "Cancel"),
+    ("oauth.authorize.previously_granted", "Previously granted"),
+    (
+        "oauth.error.invalid_scope",
+        "The application requested an invalid scope",
+    ),
+    ("password.error.too_short", "Password is too short"),
+    (
+        "password.error.classes",
+        "Password does not contain enough character classes",
+    ),
+    (
+        "password.error.username",
+        "Password must not contain the username",
+    ),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("auth.login.title", "Anmelden"),
+    ("auth.login.user", "Benutzername oder E-Mail"),
+    ("auth.login.password", "Passwort"),
+    ("auth.login.submit", "Anmelden"),
+    ("auth.register.title", "Registrieren"),
+    ("auth.register.submit", "Registrieren"),
+    ("auth.error.invalid_credentials", "Ungültige Anmeldedaten"),
+    (
+        "auth.error.maintenance",
+        "Authentra befindet sich im Wartungsmodus",
+    ),
+    ("oauth.authorize.title", "Anwendung autorisieren"),
+    ("oauth.authorize.submit", "Autorisieren"),
+    ("oauth.authorize.cancel", "Abbrechen"),
+    ("oauth.authorize.previously_granted", "Bereits erteilt"),
+    (
+        "oauth.error.invalid_scope",
+        "Die Anwendung hat einen ungültigen Scope angefordert",
+    ),
+    ("password.error.too_short", "Das Passwort ist zu kurz"),
+    (
+        "password.error.classes",
+        "Das Passwort enthält zu wenige Zeichenklassen",
+    ),
+    (
+        "password.error.username",
+        "Das Passwort darf den Benutzernamen nicht enthalten",
+    ),
+];
+
+fn catalog(locale: &str) -> &'static [(&'static str, &'static str)] {
+    match locale {
+        "en" => EN,
+        "de" => DE,
+        _ => &[],
+    }
+}
+
+pub fn is_valid_locale(locale: &str) -> bool {
+    let mut parts = locale.split('-');
+    let language = parts.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.bytes().all(|b| b.is_ascii_lowercase())
+        && parts.all(|p| (2..=8).contains(&p.len()) && p.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+fn fallbacks(locale: &str) -> Vec<&str> {
+    let prefixes = locale.match_indices('-').map(|(i, _)| &locale[..i]);
+    let mut chain = vec![DEFAULT_LOCALE];
+    for prefix in prefixes.chain([locale]) {
+        if prefix != DEFAULT_LOCALE {
+            chain.push(prefix);
+        }
+    }
+    chain
+}
+
+#[instrument(skip_all, name = "translations")]
+pub async fn translations(
+    client: &impl GenericClient,
+    locale: &str,
+) -> AppResult<HashMap<String, String>> {
+    let chain = fallbacks(locale);
+    let mut strings: HashMap<String, String> = HashMap::new();
+    for locale in &chain {
+        strings.extend(
+            catalog(locale)
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+    }
+    let stmt = client
+        .prepare_cached(
+            "select key, value from translations where locale = any($1) order by array_position($1, locale::text)",
+        )
+        .await?;
+    let rows = client.query(&stmt, &[&chain]).await?;
+    strings.extend(
+        rows.into_iter()
+            .map(|row| (row.get("key"), row.get("value"))),
+    );
+    Ok(strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fallbacks, is_valid_locale};
+
+    #[test]
+    fn locale_fallbacks() {
+        assert_eq!(fallbacks("en"), vec!["en"]);
+        assert_eq!(fallbacks("de"), vec!["en", "de"]);
+        assert_eq!(fallbacks("de-AT"), vec!["en", "de", "de-AT"]);
+    }
+
+    #[test]
+    fn validates_locales() {
+        assert!(is_valid_locale("en"));
+        assert!(is_valid_locale("de-AT"));
+        assert!(!is_valid_locale("../etc"));
+        assert!(!is_valid_locale("EN"));
+        assert!(!is_valid_locale(""));
+    }
+}
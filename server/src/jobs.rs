@@ -0,0 +1,88 @@
+use deadpool_postgres::GenericClient;
+use postgres_types::{FromSql, ToSql};
+use serde::Serialize;
+use tokio_postgres::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::AppResult;
+
+const RETENTION_SECONDS: f64 = 60.0 * 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromSql, ToSql)]
+#[postgres(name = "job_state")]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    #[postgres(name = "running")]
+    Running,
+    #[postgres(name = "completed")]
+    Completed,
+    #[postgres(name = "failed")]
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: Uuid,
+    pub kind: String,
+    pub state: JobState,
+    pub total: i64,
+    pub processed: i64,
+    pub message: Option<String>,
+}
+
+fn job_status(row: Row) -> JobStatus {
+    JobStatus {
+        id: row.get("id"),
+        kind: row.get("kind"),
+        state: row.get("state"),
+        total: row.get("total"),
+        processed: row.get("processed"),
+        message: row.get("message"),
+    }
+}
+
+#[instrument(skip_all, name = "start_job")]
+pub async fn start(client: &impl GenericClient, kind: &str, total: u64) -> AppResult<JobStatus> {
+    let stmt = client
+        .prepare_cached("delete from jobs where state <> 'running' and updated_at < now() - make_interval(secs => $1)")
+        .await?;
+    client.execute(&stmt, &[&RETENTION_SECONDS]).await?;
+    let stmt = client
+        .prepare_cached("insert into jobs(kind,total) values($1,$2) returning id,kind,state,total,processed,message")
+        .await?;
+    let row = client.query_one(&stmt, &[&kind, &(total as i64)]).await?;
+    Ok(job_status(row))
+}
+
+pub async fn progress(client: &impl GenericClient, id: &Uuid, processed: u64) -> AppResult<()> {
+    let stmt = client
+        .prepare_cached(
+            "update jobs set processed = processed + $2, updated_at = now() where id = $1",
+        )
+        .await?;
+    client.execute(&stmt, &[id, &(processed as i64)]).await?;
+    Ok(())
+}
+
+pub async fn finish(
+    client: &impl GenericClient,
+    id: &Uuid,
+    state: JobState,
+    message: &str,
+) -> AppResult<()> {
+    let stmt = client
+        .prepare_cached(
+            "update jobs set state = $2, message = $3, updated_at = now() where id = $1",
+        )
+        .await?;
+    client.execute(&stmt, &[id, &state, &message]).await?;
+    Ok(())
+}
+
+pub async fn get(client: &impl GenericClient, id: &Uuid) -> AppResult<Option<JobStatus>> {
+    let stmt = client
+        .prepare_cached("select id,kind,state,total,processed,message from jobs where id = $1")
+        .await?;
+    Ok(client.query_opt(&stmt, &[id]).await?.map(job_status))
+}
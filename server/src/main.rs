@@ -1,25 +1,41 @@
-use std::{net::SocketAddr, ops::DerefMut, process::exit};
+use std::{net::SocketAddr, ops::DerefMut, process::exit, time::Duration};
 
 use axum::{
     extract::{rejection::JsonRejection, FromRequest, FromRequestParts, Query},
     http::request::Parts,
     response::IntoResponse,
-    Json, Server,
+    Json, Router, Server,
 };
 use deadpool_postgres::{Config, Object, Pool};
 use error::Error;
 use serde::{de::DeserializeOwned, Serialize};
 use tracing::info;
 
-use crate::{auth::AuthState, config::AuthentraConfiguration};
+use crate::{
+    auth::AuthState, chaos::Chaos, config::AuthentraConfiguration, features::FeatureFlags,
+    maintenance::Maintenance, media::Media, sessions::SessionCache, shedding::LoadShedder,
+    utils::breach::BreachCheck,
+};
 
 pub mod auth;
+mod broadcast;
+mod chaos;
+mod compression;
 mod config;
 pub mod routes;
 mod state;
-pub use state::AppState;
+pub use state::{AppState, StateParts};
 pub mod error;
-mod telemetry;
+pub mod features;
+mod i18n;
+mod jobs;
+mod maintenance;
+mod media;
+mod selftest;
+mod sessions;
+mod shedding;
+pub mod telemetry;
+mod timeout;
 pub mod utils;
 
 #[tokio::main]
@@ -34,8 +50,6 @@ mod embedded {
 
 pub type AppResult<T, E = error::Error> = Result<T, E>;
 
-pub const PAGE_LIMIT: u16 = 100;
-
 macro_rules! api_extractor {
     ($name:ident, $error:ty, $ty:tt) => {
         pub struct $name<T>(T);
@@ -88,20 +102,112 @@ async fn shutdown_future() {
     };
 }
 
+fn validate_configuration(configuration: &AuthentraConfiguration) -> bool {
+    match configuration.validate() {
+        Ok(()) => true,
+        Err(errors) => {
+            for error in errors {
+                tracing::error!("Invalid configuration: {error}");
+            }
+            false
+        }
+    }
+}
+
+fn validate_configuration_only(configuration: &AuthentraConfiguration) -> ! {
+    println!("{}", configuration.report());
+    match configuration.validate() {
+        Ok(()) => {
+            println!("Configuration is valid");
+            exit(0)
+        }
+        Err(errors) => {
+            for error in errors {
+                eprintln!("Invalid configuration: {error}");
+            }
+            exit(1)
+        }
+    }
+}
+
 async fn main_tokio() {
-    let configuration = AuthentraConfiguration::load().unwrap();
+    let configuration = match AuthentraConfiguration::load() {
+        Ok(configuration) => configuration,
+        Err(err) => {
+            eprintln!("Failed to load configuration: {err}");
+            exit(1)
+        }
+    };
+    if std::env::args().any(|arg| arg == "--validate-config") {
+        validate_configuration_only(&configuration);
+    }
     telemetry::setup_tracing();
+    info!("Effective configuration:\n{}", configuration.report());
+    if !validate_configuration(&configuration) {
+        exit(1)
+    }
 
-    let pool = create_database_pool(configuration.postgres.clone());
-    {
+    let pool = create_database_pool(
+        configuration.postgres.clone(),
+        configuration.statement_timeout,
+    );
+    let settings_ttl = Duration::from_secs(configuration.settings.cache);
+    let (features, maintenance) = {
         let mut conn = pool.get().await.expect("Failed to get database connection");
         run_migrations(&mut conn).await;
-    }
-    let auth_state = AuthState::new(configuration.secret.as_str());
+        let features = FeatureFlags::load(&conn)
+            .await
+            .expect("Failed to load feature flags");
+        let maintenance = Maintenance::load(&conn, settings_ttl)
+            .await
+            .expect("Failed to load maintenance state");
+        (features, maintenance)
+    };
+    let auth_state = AuthState::new(
+        configuration.secret.as_str(),
+        configuration.issuer(),
+        configuration.session.leeway,
+    );
+    let media = Media::new(&configuration);
+    let sessions = SessionCache::new(Duration::from_secs(configuration.session.cache));
+    let chaos = Chaos::new(&configuration.chaos);
+    let breach = BreachCheck::new(&configuration.password, chaos);
+    let shedder = LoadShedder::new(configuration.shed.depth);
+
+    let state = AppState::new(StateParts {
+        pool,
+        auth: auth_state,
+        config: configuration.clone(),
+        features,
+        maintenance,
+        media,
+        sessions,
+        breach,
+        shedder,
+        chaos,
+    });
 
-    let state = AppState::new(pool, auth_state);
+    if !selftest::run(&state).await {
+        tracing::error!("Refusing to serve after failed self-tests");
+        exit(1)
+    }
 
-    let router = routes::setup_router().with_state(state);
+    match configuration.postgres.get_pg_config() {
+        Ok(config) => {
+            tokio::spawn(broadcast::listen(config, state.clone()));
+        }
+        Err(err) => tracing::warn!("Cache invalidation listener disabled: {err}"),
+    }
+    tokio::spawn(features::refresh(state.clone(), settings_ttl));
+    tokio::spawn(telemetry::metrics::serve(
+        configuration.listen.metrics,
+        state.clone(),
+    ));
+    let router = match configuration.base.as_str() {
+        "" => routes::setup_router(&state),
+        base => Router::new().nest(base, routes::setup_router(&state)),
+    }
+    .with_state(state);
     Server::bind(&configuration.listen.http)
         .serve(router.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_future())
@@ -111,7 +217,12 @@ async fn main_tokio() {
     opentelemetry::global::shutdown_tracer_provider();
 }
 
-pub fn create_database_pool(configuration: Config) -> Pool {
+pub fn create_database_pool(mut configuration: Config, statement_timeout: u64) -> Pool {
+    let timeout_option = format!("-c statement_timeout={statement_timeout}");
+    configuration.options = Some(match configuration.options.take() {
+        Some(options) => format!("{options} {timeout_option}"),
+        None => timeout_option,
+    });
     configuration
         .create_pool(
             Some(deadpool_postgres::Runtime::Tokio1),
@@ -0,0 +1,179 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::State,
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use deadpool_postgres::GenericClient;
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::{error::ApiError, AppResult, AppState};
+
+const DEFAULT_MESSAGE: &str = "Authentra is currently in maintenance mode";
+const LOGIN_PATHS: [&str; 2] = ["/api/v1/auth/login", "/api/v1/auth/browser/register"];
+const MAINTENANCE_PATH: &str = "/api/v1/admin/maintenance";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    pub message: Option<String>,
+}
+
+pub struct Maintenance {
+    enabled: AtomicBool,
+    message: RwLock<Option<String>>,
+    ttl: Duration,
+    checked: Mutex<Instant>,
+}
+
+impl Maintenance {
+    #[instrument(skip_all, name = "load_maintenance")]
+    pub async fn load(client: &impl GenericClient, ttl: Duration) -> AppResult<Self> {
+        let maintenance = Self {
+            enabled: AtomicBool::new(false),
+            message: RwLock::new(None),
+            ttl,
+            checked: Mutex::new(Instant::now()),
+        };
+        maintenance.reload(client).await?;
+        Ok(maintenance)
+    }
+
+    pub async fn reload(&self, client: &impl GenericClient) -> AppResult<()> {
+        let stmt = client
+            .prepare_cached("select maintenance,maintenance_message from settings")
+            .await?;
+        let row = client.query_opt(&stmt, &[]).await?;
+        let (enabled, message) = match row {
+            Some(row) => (row.get("maintenance"), row.get("maintenance_message")),
+            None => (false, None),
+        };
+        *self.message.write().expect("Maintenance lock poisoned") = message;
+        self.enabled.store(enabled, Ordering::Relaxed);
+        *self.checked.lock().expect("Maintenance lock poisoned") = Instant::now();
+        Ok(())
+    }
+
+    /// Claims the next refresh once the cached state is older than the ttl, so
+    /// only one request per interval goes to the database.
+    fn claim_refresh(&self) -> bool {
+        let mut checked = self.checked.lock().expect("Maintenance lock poisoned");
+        if checked.elapsed() < self.ttl {
+            return false;
+        }
+        *checked = Instant::now();
+        true
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        MaintenanceStatus {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            message: self
+                .message
+                .read()
+                .expect("Maintenance lock poisoned")
+                .clone(),
+        }
+    }
+
+    pub async fn set(
+        &self,
+        client: &impl GenericClient,
+        enabled: bool,
+        message: Option<String>,
+    ) -> AppResult<()> {
+        let stmt = client
+            .prepare_cached("update settings set maintenance = $1, maintenance_message = $2")
+            .await?;
+        client.execute(&stmt, &[&enabled, &message]).await?;
+        *self.message.write().expect("Maintenance lock poisoned") = message;
+        self.enabled.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn is_blocked(method: &Method, path: &str) -> bool {
+    if LOGIN_PATHS.contains(&path) {
+        return true;
+    }
+    let allowed = match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS => true,
+        Method::PUT => path == MAINTENANCE_PATH,
+        // Admins must be able to sign in and elevate to turn maintenance off.
+        Method::POST => matches!(
+            path,
+            "/api/internal/oauth/authorize"
+                | "/api/v1/password/strength"
+                | "/api/v1/auth/browser/login"
+                | "/api/v1/auth/browser/admin"
+        ),
+        Method::DELETE => matches!(
+            path,
+            "/api/v1/auth/browser/logout" | "/api/v1/auth/browser/admin"
+        ),
+        _ => false,
+    };
+    !allowed
+}
+
+pub async fn guard<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> AppResult<Response> {
+    let maintenance = state.maintenance();
+    if maintenance.claim_refresh() {
+        let reloaded = match state.conn().await {
+            Ok(conn) => maintenance.reload(&conn).await,
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = reloaded {
+            tracing::warn!("Failed to refresh maintenance state: {err}");
+        }
+    }
+    if maintenance.enabled.load(Ordering::Relaxed)
+        && is_blocked(request.method(), request.uri().path())
+    {
+        let message = maintenance.status().message;
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            message.as_deref().unwrap_or(DEFAULT_MESSAGE),
+        )
+        .into());
+    }
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+
+    use super::is_blocked;
+
+    #[test]
+    fn blocks_mutations_outside_allowlist() {
+        assert!(is_blocked(&Method::POST, "/api/v1/auth/login"));
+        assert!(is_blocked(&Method::POST, "/api/v1/sessions/revoke"));
+        assert!(is_blocked(
+            &Method::POST,
+            "/api/v1/system/tasks/purge-sessions"
+        ));
+        assert!(is_blocked(&Method::PUT, "/api/v1/me/password"));
+        assert!(!is_blocked(&Method::POST, "/api/v1/auth/browser/login"));
+        assert!(!is_blocked(&Method::POST, "/api/v1/auth/browser/admin"));
+        assert!(!is_blocked(&Method::DELETE, "/api/v1/auth/browser/admin"));
+        assert!(!is_blocked(&Method::GET, "/api/v1/users"));
+        assert!(!is_blocked(&Method::GET, "/api/v1/auth/browser/refresh"));
+        assert!(!is_blocked(&Method::PUT, "/api/v1/admin/maintenance"));
+        assert!(!is_blocked(&Method::POST, "/api/internal/oauth/authorize"));
+    }
+}
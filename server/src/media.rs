@@ -0,0 +1,295 @@
+use std::{
+    io::{self, ErrorKind as IoErrorKind},
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::StatusCode;
+use jsonwebtoken::{TokenData, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::instrument;
+
+use crate::{
+    auth::{jwt_header, validation, AuthState},
+    config::{AuthentraConfiguration, MediaConfiguration},
+    error::ApiError,
+    AppResult,
+};
+
+mod s3;
+
+pub use s3::S3Storage;
+
+const CONTENT_TYPES: [(&str, &str); 4] = [
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/webp", "webp"),
+    ("image/gif", "gif"),
+];
+const KEY_LENGTH: usize = 32;
+const GRAVATAR_URL: &str = "https://www.gravatar.com/avatar";
+const MEDIA_AUDIENCE: &str = "media";
+const PROBE_KEY: &str = ".selftest";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaBackend {
+    Local,
+    S3,
+}
+
+#[axum::async_trait]
+pub trait MediaStorage: Send + Sync {
+    async fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    async fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+#[axum::async_trait]
+impl MediaStorage for LocalStorage {
+    async fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.root.join(key);
+        tokio::fs::create_dir_all(&self.root).await?;
+        let mut partial = PartialFile {
+            path: self.root.join(format!("{key}.partial")),
+            renamed: false,
+        };
+        tokio::fs::write(&partial.path, bytes).await?;
+        tokio::fs::rename(&partial.path, &path).await?;
+        partial.renamed = true;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.root.join(key)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == IoErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == IoErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Removes an upload's partial file unless it was renamed into place, so
+/// failed or cancelled writes don't accumulate in the media directory.
+struct PartialFile {
+    path: PathBuf,
+    renamed: bool,
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if self.renamed {
+            return;
+        }
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            if err.kind() != IoErrorKind::NotFound {
+                tracing::warn!("Failed to remove partial media file: {err}");
+            }
+        }
+    }
+}
+
+pub struct Media {
+    base: String,
+    storage: Box<dyn MediaStorage>,
+    size: usize,
+    lifetime: Duration,
+    gravatar: bool,
+    validation: Validation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MediaClaims {
+    iss: String,
+    aud: String,
+    exp: u64,
+    nbf: u64,
+    sub: String,
+}
+
+fn extension(content_type: &str) -> Option<&'static str> {
+    CONTENT_TYPES
+        .iter()
+        .find(|(ty, _)| *ty == content_type)
+        .map(|(_, ext)| *ext)
+}
+
+fn gravatar_url(email: &str) -> String {
+    let digest = Sha256::digest(email.trim().to_lowercase().as_bytes());
+    let hash: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("{GRAVATAR_URL}/{hash}?d=identicon")
+}
+
+fn content_type(key: &str) -> Option<&'static str> {
+    let (name, ext) = key.split_once('.')?;
+    if name.len() != KEY_LENGTH || !name.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    CONTENT_TYPES
+        .iter()
+        .find(|(_, e)| *e == ext)
+        .map(|(ty, _)| *ty)
+}
+
+fn storage(media: &MediaConfiguration) -> Box<dyn MediaStorage> {
+    match media.storage {
+        MediaBackend::Local => Box::new(LocalStorage::new(media.path.clone())),
+        MediaBackend::S3 => Box::new(S3Storage::new(
+            media.endpoint.as_deref().unwrap_or_default(),
+            media.bucket.as_deref().unwrap_or_default(),
+            &media.region,
+            media.access.as_deref().unwrap_or_default(),
+            media.secret.as_deref().unwrap_or_default(),
+        )),
+    }
+}
+
+impl Media {
+    pub fn new(configuration: &AuthentraConfiguration) -> Self {
+        let mut validation = validation(&configuration.issuer(), configuration.session.leeway);
+        validation.set_audience(&[MEDIA_AUDIENCE]);
+        Self {
+            base: configuration.public_url(),
+            storage: storage(&configuration.media),
+            size: configuration.media.size,
+            lifetime: Duration::from_secs(configuration.media.lifetime),
+            gravatar: configuration.media.gravatar,
+            validation,
+        }
+    }
+
+    /// Writes and removes a probe object to check that the storage is usable.
+    pub async fn probe(&self) -> io::Result<()> {
+        self.storage.put(PROBE_KEY, b"ok").await?;
+        self.storage.delete(PROBE_KEY).await
+    }
+
+    pub fn validate(&self, content_type: &str, len: usize) -> AppResult<&'static str> {
+        let Some(ext) = extension(content_type) else {
+            return Err(ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Unsupported content type '{content_type}'"),
+            )
+            .into());
+        };
+        if len == 0 {
+            return Err(ApiError::new(StatusCode::BAD_REQUEST, "Empty upload").into());
+        }
+        if len > self.size {
+            return Err(ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Upload exceeds {} bytes", self.size),
+            )
+            .into());
+        }
+        Ok(ext)
+    }
+
+    #[instrument(skip_all, name = "media_put")]
+    pub async fn put(&self, content_type: &str, bytes: &[u8]) -> AppResult<String> {
+        let ext = self.validate(content_type, bytes.len())?;
+        let key = format!("{:032x}.{ext}", rand::random::<u128>());
+        self.storage.put(&key, bytes).await?;
+        Ok(key)
+    }
+
+    #[instrument(skip_all, name = "media_get")]
+    pub async fn get(&self, key: &str) -> AppResult<Option<(Vec<u8>, &'static str)>> {
+        let Some(content_type) = content_type(key) else { return Ok(None) };
+        Ok(self
+            .storage
+            .get(key)
+            .await?
+            .map(|bytes| (bytes, content_type)))
+    }
+
+    #[instrument(skip_all, name = "media_delete")]
+    pub async fn delete(&self, key: &str) -> AppResult<()> {
+        if content_type(key).is_none() {
+            return Ok(());
+        }
+        Ok(self.storage.delete(key).await?)
+    }
+
+    pub fn lifetime(&self) -> Duration {
+        self.lifetime
+    }
+
+    pub fn signed_url(&self, auth: &AuthState, key: &str) -> AppResult<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Failed to get time since epoch");
+        let claims = MediaClaims {
+            iss: auth.issuer().into(),
+            aud: MEDIA_AUDIENCE.into(),
+            exp: (now + self.lifetime).as_secs(),
+            nbf: now.as_secs(),
+            sub: key.into(),
+        };
+        let token = jsonwebtoken::encode(&jwt_header(), &claims, auth.encoding())?;
+        Ok(format!("{}/api/v1/media/{key}?token={token}", self.base))
+    }
+
+    pub fn avatar_url(
+        &self,
+        auth: &AuthState,
+        avatar: Option<&str>,
+        email: Option<&str>,
+    ) -> AppResult<Option<String>> {
+        match (avatar, email) {
+            (Some(key), _) => self.signed_url(auth, key).map(Some),
+            (None, Some(email)) if self.gravatar => Ok(Some(gravatar_url(email))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn verify(&self, auth: &AuthState, key: &str, token: &str) -> AppResult<()> {
+        let data: TokenData<MediaClaims> =
+            jsonwebtoken::decode(token, auth.decoding(), &self.validation)?;
+        if data.claims.sub != key {
+            return Err(ApiError::new(StatusCode::FORBIDDEN, "Invalid media signature").into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_type, gravatar_url};
+
+    #[test]
+    fn rejects_untrusted_keys() {
+        let name = "0123456789abcdef0123456789abcdef";
+        assert_eq!(content_type(&format!("{name}.png")), Some("image/png"));
+        assert_eq!(content_type(&format!("{name}.exe")), None);
+        assert_eq!(content_type("../../etc/passwd.png"), None);
+        assert_eq!(content_type(&format!("../{name}.png")), None);
+    }
+
+    #[test]
+    fn normalizes_gravatar_email() {
+        assert_eq!(
+            gravatar_url(" User@Example.com "),
+            gravatar_url("user@example.com")
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use super::MediaStorage;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Stores media in an S3-compatible bucket using path-style requests signed
+/// with AWS Signature Version 4.
+pub struct S3Storage {
+    client: reqwest::Client,
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access: String,
+    secret: String,
+}
+
+impl S3Storage {
+    pub fn new(endpoint: &str, bucket: &str, region: &str, access: &str, secret: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            access: access.to_string(),
+            secret: secret.to_string(),
+        }
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Option<Vec<u8>>,
+    ) -> io::Result<reqwest::Response> {
+        let url = Url::parse(&format!("{}/{}/{key}", self.endpoint, self.bucket))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "S3 endpoint has no host",
+                ))
+            }
+        };
+        let date = amz_date(SystemTime::now());
+        let payload = hex(&Sha256::digest(body.as_deref().unwrap_or_default()));
+        let canonical = format!(
+            "{method}\n{}\n\nhost:{host}\nx-amz-content-sha256:{payload}\nx-amz-date:{date}\n\n{SIGNED_HEADERS}\n{payload}",
+            url.path()
+        );
+        let scope = format!("{}/{}/s3/aws4_request", &date[..8], self.region);
+        let to_sign = format!(
+            "{ALGORITHM}\n{date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical.as_bytes()))
+        );
+        let key = signing_key(&self.secret, &date[..8], &self.region, "s3");
+        let signature = hex(&hmac(&key, to_sign.as_bytes()));
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload)
+            .header("x-amz-date", date)
+            .header(
+                "authorization",
+                format!(
+                    "{ALGORITHM} Credential={}/{scope}, SignedHeaders={SIGNED_HEADERS}, Signature={signature}",
+                    self.access
+                ),
+            );
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        request
+            .send()
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(data)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let key = hmac(format!("AWS4{secret}").as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+fn amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .expect("Failed to get time since epoch")
+        .as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from days since the epoch, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+fn status_error(status: StatusCode) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("S3 request failed with status {status}"),
+    )
+}
+
+#[axum::async_trait]
+impl MediaStorage for S3Storage {
+    async fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let response = self.send(Method::PUT, key, Some(bytes.to_vec())).await?;
+        if !response.status().is_success() {
+            return Err(status_error(response.status()));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let response = self.send(Method::GET, key, None).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(
+                response
+                    .bytes()
+                    .await
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+                    .to_vec(),
+            )),
+            status => Err(status_error(status)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        let response = self.send(Method::DELETE, key, None).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(()),
+            status if status.is_success() => Ok(()),
+            status => Err(status_error(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{amz_date, hex, hmac, signing_key};
+
+    #[test]
+    fn computes_hmac_sha256() {
+        assert_eq!(
+            hex(&hmac(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn derives_signing_key() {
+        assert_eq!(
+            hex(&signing_key(
+                "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                "20120215",
+                "us-east-1",
+                "iam"
+            )),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn formats_amz_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_329_305_445);
+        assert_eq!(amz_date(time), "20120215T113045Z");
+        let leap = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(amz_date(leap), "20000229T000000Z");
+    }
+}
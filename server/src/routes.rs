@@ -1,18 +1,28 @@
 use std::str::FromStr;
 
-use axum::{routing::get, Router};
+use axum::{extract::DefaultBodyLimit, middleware, routing::get, Router};
 use derive_more::Display;
 use postgres_types::{FromSql, ToSql};
 use serde::{Deserialize, Serialize};
 use tower::ServiceBuilder;
 use tracing::instrument;
 
-use crate::AppState;
+use crate::{compression::BodyLimits, AppState};
 mod admin;
 mod application_groups;
 mod applications;
 mod auth;
+mod discovery;
+mod i18n;
+mod login_history;
+mod me;
+mod media;
+mod notifications;
 pub mod oauth;
+pub mod pagination;
+pub mod password;
+mod sessions;
+mod system;
 mod user;
 
 #[derive(
@@ -61,7 +71,7 @@ impl InternalScope {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, FromSql, ToSql)]
+#[derive(Debug, Serialize, Deserialize, FromSql, ToSql, PartialEq, Eq)]
 #[postgres(name = "application_kind")]
 pub enum ApplicationKind {
     #[postgres(name = "web-server")]
@@ -70,15 +80,19 @@ pub enum ApplicationKind {
     #[postgres(name = "spa")]
     #[serde(rename = "spa")]
     SPA,
+    #[postgres(name = "native")]
+    #[serde(rename = "native")]
+    Native,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromSql, ToSql)]
+#[derive(Debug, Serialize, Deserialize, FromSql, ToSql, PartialEq, Eq, Default)]
 #[postgres(name = "consent_mode")]
 #[serde(rename_all = "lowercase")]
 pub enum ConsentMode {
-    #[postgres(name = "web-server")]
+    #[default]
+    #[postgres(name = "explicit")]
     Explicit,
-    #[postgres(name = "spa")]
+    #[postgres(name = "implicit")]
     Implicit,
 }
 
@@ -96,18 +110,167 @@ impl FromStr for InternalScope {
     }
 }
 
-pub fn setup_router() -> Router<AppState> {
+pub fn setup_router(state: &AppState) -> Router<AppState> {
     let middlewares = ServiceBuilder::new().layer(crate::telemetry::middleware::new());
+    let admin_network = middleware::from_fn_with_state(state.clone(), admin::network_guard);
+    let timeout = |endpoint: &str| {
+        middleware::from_fn_with_state(
+            state.config().timeout.budget(endpoint),
+            crate::timeout::guard,
+        )
+    };
+    let shed = |group: &'static str| {
+        middleware::from_fn_with_state((state.clone(), group), crate::shedding::guard)
+    };
     Router::new()
-        .nest("/api/v1/auth", auth::router())
-        .nest("/api/v1/users", user::router())
-        .nest("/api/v1/admin", admin::router())
-        .nest("/api/internal/oauth", oauth::router())
-        .nest("/api/v1/applications", applications::router())
-        .nest("/api/v1/application-groups", application_groups::router())
+        .nest("/api/v1/auth", auth::router().route_layer(timeout("auth")))
+        .nest(
+            "/api/v1/users",
+            user::router()
+                .merge(
+                    user::admin_router()
+                        .route_layer(admin_network.clone())
+                        .route_layer(shed("users")),
+                )
+                .route_layer(timeout("users")),
+        )
+        .nest(
+            "/api/v1/admin",
+            admin::router()
+                .route_layer(admin_network.clone())
+                .route_layer(shed("admin"))
+                .route_layer(timeout("admin")),
+        )
+        .nest(
+            "/api/v1/me",
+            me::router(state.config().media.size).route_layer(timeout("me")),
+        )
+        .nest(
+            "/api/internal/oauth",
+            oauth::router().route_layer(timeout("oauth")),
+        )
+        .nest(
+            "/api/v1/applications",
+            applications::router()
+                .route_layer(shed("applications"))
+                .route_layer(timeout("applications")),
+        )
+        .nest(
+            "/api/v1/application-groups",
+            application_groups::router()
+                .route_layer(admin_network.clone())
+                .route_layer(shed("groups"))
+                .route_layer(timeout("groups")),
+        )
+        .nest(
+            "/api/v1/sessions",
+            sessions::router()
+                .route_layer(admin_network.clone())
+                .route_layer(timeout("sessions")),
+        )
+        .nest(
+            "/api/v1/system",
+            system::router()
+                .route_layer(admin_network)
+                .route_layer(timeout("system")),
+        )
+        .nest(
+            "/api/v1/password",
+            password::router().route_layer(timeout("password")),
+        )
+        .nest(
+            "/api/v1/media",
+            media::router().route_layer(timeout("media")),
+        )
+        .nest("/api/v1/i18n", i18n::router().route_layer(timeout("i18n")))
+        .nest(
+            "/.well-known",
+            discovery::router().route_layer(timeout("discovery")),
+        )
         .route("/api/internal/health", get(health))
+        .layer(middleware::from_fn_with_state(
+            BodyLimits::new(state.config().body.limit)
+                .route("/api/v1/me/avatar", state.config().media.size),
+            crate::compression::decompress,
+        ))
+        .layer(DefaultBodyLimit::max(state.config().body.limit))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::maintenance::guard,
+        ))
+        .layer(crate::compression::layer(
+            state.config().compression.threshold,
+        ))
         .layer(middlewares)
 }
 async fn health() -> &'static str {
     ""
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::{ApplicationKind, ConsentMode, InternalScope};
+    use crate::{
+        auth::UserRole,
+        features::Feature,
+        routes::{notifications::Severity, oauth::CodeChallengeMethod},
+    };
+
+    fn assert_external<T>(value: T, name: &str)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let encoded = serde_json::to_string(&value).unwrap();
+        assert_eq!(encoded, format!("\"{name}\""));
+        assert_eq!(serde_json::from_str::<T>(&encoded).unwrap(), value);
+    }
+
+    #[test]
+    fn stable_enum_serialization() {
+        assert_external(ApplicationKind::WebServer, "web-server");
+        assert_external(ApplicationKind::SPA, "spa");
+        assert_external(ApplicationKind::Native, "native");
+        assert_external(ConsentMode::Explicit, "explicit");
+        assert_external(ConsentMode::Implicit, "implicit");
+        assert_external(InternalScope::Email, "email");
+        assert_external(InternalScope::ProfileRead, "profile:read");
+        assert_external(InternalScope::ProfileWrite, "profile:write");
+        assert_external(UserRole::Logs, "logs");
+        assert_external(UserRole::Developer, "developer");
+        assert_external(UserRole::Admin, "admin");
+        assert_external(Feature::NativeApplications, "native_applications");
+        assert_external(Feature::DeviceAlerts, "device_alerts");
+        assert_external(CodeChallengeMethod::Plain, "plain");
+        assert_external(CodeChallengeMethod::S256, "S256");
+        assert_external(Severity::Info, "info");
+        assert_external(Severity::Warning, "warning");
+        assert_external(Severity::Critical, "critical");
+    }
+
+    #[test]
+    fn display_matches_serialization() {
+        for scope in [
+            InternalScope::Email,
+            InternalScope::ProfileRead,
+            InternalScope::ProfileWrite,
+        ] {
+            assert_eq!(
+                serde_json::to_string(&scope).unwrap(),
+                format!("\"{scope}\"")
+            );
+        }
+        for role in [UserRole::Logs, UserRole::Developer, UserRole::Admin] {
+            assert_eq!(serde_json::to_string(&role).unwrap(), format!("\"{role}\""));
+        }
+        for feature in Feature::values() {
+            assert_eq!(
+                serde_json::to_string(&feature).unwrap(),
+                format!("\"{feature}\"")
+            );
+        }
+    }
+}
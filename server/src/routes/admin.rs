@@ -1,7 +1,186 @@
-use axum::Router;
+use std::net::SocketAddr;
 
-use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::Response,
+    routing::{get, put},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    auth::ApiAuth,
+    error::{ApiError, ErrorKind},
+    features::Feature,
+    i18n::is_valid_locale,
+    maintenance::MaintenanceStatus,
+    routes::notifications::{self, Severity},
+    utils::network::{client_ip, matches_any},
+    ApiJson, ApiResponse, AppResult, AppState,
+};
 
 pub fn router() -> Router<AppState> {
     Router::new()
+        .route("/features", get(features))
+        .route("/features/:name", put(set_feature))
+        .route("/maintenance", get(maintenance).put(set_maintenance))
+        .route(
+            "/translations/:locale/:key",
+            put(set_translation).delete(delete_translation),
+        )
+}
+
+#[derive(Serialize)]
+struct EncodedFeature {
+    name: Feature,
+    enabled: bool,
+}
+
+async fn features(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+) -> AppResult<ApiResponse<Vec<EncodedFeature>>> {
+    auth.check_admin()?;
+    Ok(ApiResponse(
+        state
+            .features()
+            .all()
+            .into_iter()
+            .map(|(name, enabled)| EncodedFeature { name, enabled })
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct FeaturePayload {
+    enabled: bool,
+}
+
+#[instrument(skip_all, name = "set_feature", fields(feature = %name))]
+async fn set_feature(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    Path(name): Path<Feature>,
+    ApiJson(payload): ApiJson<FeaturePayload>,
+) -> AppResult<ApiResponse<EncodedFeature>> {
+    auth.check_admin()?;
+    let conn = state.conn().await?;
+    state.features().set(&conn, name, payload.enabled).await?;
+    tracing::info!(enabled = payload.enabled, "Feature flag changed");
+    Ok(ApiResponse(EncodedFeature {
+        name,
+        enabled: payload.enabled,
+    }))
+}
+
+async fn maintenance(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+) -> AppResult<ApiResponse<MaintenanceStatus>> {
+    auth.check_admin()?;
+    Ok(ApiResponse(state.maintenance().status()))
+}
+
+#[derive(Deserialize)]
+struct MaintenancePayload {
+    enabled: bool,
+    message: Option<String>,
+}
+
+#[instrument(skip_all, name = "set_maintenance")]
+async fn set_maintenance(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    ApiJson(payload): ApiJson<MaintenancePayload>,
+) -> AppResult<ApiResponse<MaintenanceStatus>> {
+    auth.check_admin()?;
+    let conn = state.conn().await?;
+    state
+        .maintenance()
+        .set(&conn, payload.enabled, payload.message)
+        .await?;
+    tracing::warn!(enabled = payload.enabled, "Maintenance mode changed");
+    let title = if payload.enabled {
+        "Maintenance mode enabled"
+    } else {
+        "Maintenance mode disabled"
+    };
+    notifications::notify_admins(
+        &conn,
+        Severity::Info,
+        title,
+        &format!("Changed by user {}", auth.user),
+    )
+    .await?;
+    Ok(ApiResponse(state.maintenance().status()))
+}
+
+#[derive(Deserialize)]
+struct TranslationPayload {
+    value: String,
+}
+
+#[instrument(skip_all, name = "set_translation", fields(locale = %locale, key = %key))]
+async fn set_translation(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    Path((locale, key)): Path<(String, String)>,
+    ApiJson(payload): ApiJson<TranslationPayload>,
+) -> AppResult<ApiResponse<()>> {
+    auth.check_admin()?;
+    if !is_valid_locale(&locale) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid locale '{locale}'"),
+        )
+        .into());
+    }
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached(
+            "insert into translations(locale,key,value) values($1,$2,$3) on conflict (locale, key) do update set value = excluded.value, updated_at = now()",
+        )
+        .await?;
+    conn.execute(&stmt, &[&locale, &key, &payload.value])
+        .await?;
+    Ok(ApiResponse(()))
+}
+
+#[instrument(skip_all, name = "delete_translation", fields(locale = %locale, key = %key))]
+async fn delete_translation(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    Path((locale, key)): Path<(String, String)>,
+) -> AppResult<ApiResponse<()>> {
+    auth.check_admin()?;
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached("delete from translations where locale = $1 and key = $2")
+        .await?;
+    if conn.execute(&stmt, &[&locale, &key]).await? == 0 {
+        return Err(ErrorKind::not_found().into());
+    }
+    Ok(ApiResponse(()))
+}
+
+#[instrument(skip_all, name = "admin_network_guard", fields(client_ip))]
+pub async fn network_guard<B>(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> AppResult<Response> {
+    let config = state.config();
+    let ip = client_ip(peer.ip(), request.headers(), &config.trusted_proxies);
+    tracing::Span::current().record("client_ip", ip.to_string());
+    if matches_any(&config.admin_deny, ip)
+        || (!config.admin_allow.is_empty() && !matches_any(&config.admin_allow, ip))
+    {
+        tracing::warn!("Admin request from disallowed address");
+        return Err(ErrorKind::forbidden().into());
+    }
+    Ok(next.run(request).await)
 }
@@ -12,7 +12,8 @@ use uuid::Uuid;
 use crate::{
     auth::{ApiAuth, SessionInfo, UserRole},
     error::{ApiError, Error, ErrorKind},
-    routes::ApplicationKind,
+    features::Feature,
+    routes::{oauth::is_valid_redirect_uri, ApplicationKind, ConsentMode},
     ApiJson, ApiResponse, AppResult, AppState,
 };
 
@@ -69,6 +70,17 @@ impl AppInfo {
     }
 }
 
+fn check_redirect_uris(kind: &ApplicationKind, uris: &[String]) -> AppResult<()> {
+    match uris.iter().find(|uri| !is_valid_redirect_uri(kind, uri)) {
+        Some(uri) => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid redirect_uri '{uri}'"),
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
 impl TryFrom<Option<Row>> for AppInfo {
     type Error = Error;
 
@@ -145,6 +157,11 @@ async fn replace(
     auth.check_developer()?;
     let conn = state.conn().await?;
     AppInfo::check_by_id(&conn, &auth, &id).await?;
+    let stmt = conn
+        .prepare_cached("select kind from applications where id = $1")
+        .await?;
+    let kind: ApplicationKind = conn.query_one(&stmt, &[&id]).await?.get("kind");
+    check_redirect_uris(&kind, &payload.redirect_uri)?;
     let stmt = conn
         .prepare_cached("update applications set name = $2, redirect_uri = $3 where id = $1")
         .await?;
@@ -183,6 +200,8 @@ struct CreatePayload {
     redirect_uri: Vec<String>,
     #[serde(default)]
     system_application: bool,
+    #[serde(default)]
+    consent_mode: ConsentMode,
 }
 
 async fn create(
@@ -195,24 +214,49 @@ async fn create(
     } else {
         auth.check_developer()?;
     }
+    if payload.kind == ApplicationKind::Native
+        && !state.feature_enabled(Feature::NativeApplications)
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Native applications are not enabled",
+        )
+        .into());
+    }
+    check_redirect_uris(&payload.kind, &payload.redirect_uri)?;
     let conn = state.conn().await?;
     let stmt = if auth.has_role(UserRole::Admin) {
-        conn.prepare_cached("select id from application_groups where id = $1")
-            .await?
+        conn.prepare_cached(
+            "select id,allow_implicit_consent from application_groups where id = $1",
+        )
+        .await?
     } else {
-        conn.prepare_cached("select id from developer_allowed_groups where id = $1")
+        conn.prepare_cached("select g.id,g.allow_implicit_consent from developer_allowed_groups d join application_groups g on g.id = d.id where d.id = $1")
             .await?
     };
     let row = conn.query_opt(&stmt, &[&payload.application_group]).await?;
-    if row.is_none() {
+    let Some(group) = row else {
         return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
             format!("Unknown application_group '{}'", payload.application_group),
         )
         .into());
+    };
+    if payload.consent_mode == ConsentMode::Implicit {
+        auth.check_admin()?;
+        if !group.get::<_, bool>("allow_implicit_consent") {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Application group '{}' does not allow implicit consent",
+                    payload.application_group
+                ),
+            )
+            .into());
+        }
     }
     let stmt = conn
-        .prepare_cached("insert into applications(name,application_group, owner, kind, redirect_uri,client_secret,consent_mode,system_application) values($1,$2,$3,$4,$5,$6, 'explicit', $7) on conflict do nothing returning *")
+        .prepare_cached("insert into applications(name,application_group, owner, kind, redirect_uri,client_secret,consent_mode,system_application) values($1,$2,$3,$4,$5,$6,$7,$8) on conflict do nothing returning *")
         .await?;
     let row = conn
         .query_one(
@@ -224,6 +268,7 @@ async fn create(
                 &payload.kind,
                 &payload.redirect_uri,
                 &None::<String>,
+                &payload.consent_mode,
                 &payload.system_application,
             ],
         )
@@ -1,6 +1,11 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
 use axum::{
-    extract::State,
-    http::request::Parts,
+    extract::{ConnectInfo, Path, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{delete, get, post},
     Router,
@@ -20,19 +25,35 @@ use tracing::instrument;
 use uuid::Uuid;
 
 use crate::{
-    auth::{jwt_header, AuthError, AuthentraClaims, Claims, CookieAuth, SESSION_COOKIE},
-    utils::password::{handle_result, hash_password, verify_password},
+    auth::{
+        jwt_header, AuthError, AuthentraClaims, Claims, CookieAuth, UserRole, ADMIN_SESSION_COOKIE,
+        SESSION_COOKIE,
+    },
+    error::{ApiError, ErrorKind},
+    features::Feature,
+    routes::{
+        login_history::{self, NewLogin},
+        notifications::{self, Severity},
+    },
+    telemetry::decision::Decision,
+    utils::network::client_ip,
+    utils::password::{check_password_policy, handle_result, hash_password, verify_password},
     ApiJson, ApiResponse, AppResult, AppState,
 };
 
+/// How long the link in a new-device alert can be used to report the sign-in.
+const REPORT_TOKEN_LIFETIME: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/browser/refresh", get(refresh))
+        .route("/browser/admin", post(elevate).delete(drop_elevation))
         .route("/browser/login", post(browser_login))
         .route("/browser/register", post(register))
         .route("/browser/logout", delete(logout))
         .route("/login", post(api_login))
         .route("/registration", get(registration_enabled))
+        .route("/report/:token", post(report_device))
 }
 
 #[derive(Deserialize)]
@@ -46,6 +67,33 @@ pub struct RegisterPayload {
     password: String,
 }
 
+struct LoginClient {
+    address: IpAddr,
+    user_agent: String,
+    device_alerts: bool,
+    history_retention: u64,
+    public_url: String,
+}
+
+impl LoginClient {
+    fn new(state: &AppState, peer: SocketAddr, headers: &HeaderMap) -> Self {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .chars()
+            .take(512)
+            .collect();
+        Self {
+            address: client_ip(peer.ip(), headers, &state.config().trusted_proxies),
+            user_agent,
+            device_alerts: state.feature_enabled(Feature::DeviceAlerts),
+            history_retention: state.config().session.history,
+            public_url: state.config().public_url(),
+        }
+    }
+}
+
 fn failed<T>() -> AppResult<T> {
     Err(AuthError::InvalidCredentials.into())
 }
@@ -58,74 +106,222 @@ async fn registration_enabled() -> AppResult<ApiResponse<bool>> {
 async fn handle_login(
     conn: &impl GenericClient,
     payload: LoginPayload,
+    client: LoginClient,
 ) -> AppResult<ApiResponse<String>> {
     let stmt = conn
         .prepare_cached("select id,password from users where name = $1")
         .await?;
     let row = conn.query_opt(&stmt, &[&payload.user]).await?;
-    match row {
-        Some(row) => {
-            let uid: Uuid = row.get("id");
-            let Some(password): Option<String> = row.get("password") else { return failed() };
-            let passed = tokio::task::spawn_blocking(move || {
-                handle_result(verify_password(
-                    password.as_str(),
-                    payload.password.as_bytes(),
-                ))
-            })
-            .await??;
-            let token = {
-                let mut rng = thread_rng();
-                Alphanumeric.sample_string(&mut rng, 255)
-            };
-            let stmt = conn
-                .prepare_cached("insert into sessions(user_id,token,address) values($1, $2, null)")
-                .await?;
-            conn.execute(&stmt, &[&uid, &token]).await?;
-            // passed.map_or_else(
-            //     || failed(),
-            //     |_| Ok((make_cookies(token), ApiResponse(())).into_response()),
-            // )
-            passed.map_or_else(|| failed(), |_| Ok(ApiResponse(token)))
-            // todo!()
-        }
-        None => failed(),
+    let mut decision = Decision::new("login");
+    let Some(row) = row else {
+        decision.step("user", false);
+        decision.deny("unknown_user");
+        return failed();
+    };
+    decision.step("user", true);
+    let uid: Uuid = row.get("id");
+    decision.subject(uid);
+    let password: Option<String> = row.get("password");
+    let Some(password) = password else {
+        decision.step("password_set", false);
+        decision.deny("no_password");
+        return failed();
+    };
+    decision.step("password_set", true);
+    let passed = tokio::task::spawn_blocking(move || {
+        handle_result(verify_password(
+            password.as_str(),
+            payload.password.as_bytes(),
+        ))
+    })
+    .await??;
+    if !decision.step("password", passed.is_some()) {
+        decision.deny("invalid_password");
+        return failed();
+    }
+    let token = {
+        let mut rng = thread_rng();
+        Alphanumeric.sample_string(&mut rng, 255)
+    };
+    let stmt = conn
+        .prepare_cached("insert into sessions(user_id,token,address,user_agent) values($1, $2, $3, $4) returning id")
+        .await?;
+    let session: Uuid = conn
+        .query_one(&stmt, &[&uid, &token, &client.address, &client.user_agent])
+        .await?
+        .get("id");
+    let device = record_device(conn, &uid, &session, &client).await?;
+    login_history::record(
+        conn,
+        NewLogin {
+            user: &uid,
+            address: &client.address,
+            user_agent: &client.user_agent,
+            application: None,
+            device: Some(&device),
+            factors: &["password"],
+        },
+        client.history_retention,
+    )
+    .await?;
+    decision.allow();
+    Ok(ApiResponse(token))
+}
+
+#[instrument(skip_all, name = "record_device", fields(user = %user))]
+async fn record_device(
+    conn: &impl GenericClient,
+    user: &Uuid,
+    session: &Uuid,
+    client: &LoginClient,
+) -> AppResult<Uuid> {
+    let stmt = conn
+        .prepare_cached(
+            "insert into user_devices(user_id,user_agent,address,session) values($1,$2,$3,$4) on conflict (user_id,user_agent,address) do update set last_seen = now(), session = excluded.session returning id,report_token, (xmax = 0) as inserted",
+        )
+        .await?;
+    let row = conn
+        .query_one(&stmt, &[user, &client.user_agent, &client.address, session])
+        .await?;
+    let device: Uuid = row.get("id");
+    if !client.device_alerts || !row.get::<_, bool>("inserted") {
+        return Ok(device);
+    }
+    if login_history::has_history(conn, user).await? {
+        tracing::warn!(
+            %device,
+            address = %client.address,
+            user_agent = client.user_agent,
+            "New sign-in device"
+        );
+        let token: &str = row.get("report_token");
+        notifications::notify_user(
+            conn,
+            user,
+            Severity::Warning,
+            "New sign-in",
+            &format!(
+                "New sign-in from {} ({}). If this wasn't you, report it at {}/report/{token}",
+                client.address, client.user_agent, client.public_url
+            ),
+        )
+        .await?;
+        notifications::notify_admins(
+            conn,
+            Severity::Info,
+            "New sign-in device",
+            &format!(
+                "User {user} signed in from a new device at {} ({})",
+                client.address, client.user_agent
+            ),
+        )
+        .await?;
     }
+    Ok(device)
 }
+
+#[instrument(skip_all, name = "report_device_handler")]
+async fn report_device(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> AppResult<ApiResponse<()>> {
+    let mut conn = state.conn().await?;
+    let tx = conn.transaction().await?;
+    let stmt = tx
+        .prepare_cached("update user_devices set report_token = null where report_token = $1 and first_seen > now() - make_interval(secs => $2) returning user_id,session")
+        .await?;
+    let lifetime = REPORT_TOKEN_LIFETIME.as_secs() as f64;
+    let Some(row) = tx.query_opt(&stmt, &[&token, &lifetime]).await? else { return Err(ErrorKind::not_found().into()) };
+    let user: Uuid = row.get("user_id");
+    let session: Option<Uuid> = row.get("session");
+    let stmt = tx
+        .prepare_cached("delete from sessions where id = $1")
+        .await?;
+    tx.execute(&stmt, &[&session]).await?;
+    if let Some(session) = &session {
+        state.sessions().invalidate(session);
+    }
+    let stmt = tx
+        .prepare_cached("update users set require_password_reset = true where id = $1")
+        .await?;
+    tx.execute(&stmt, &[&user]).await?;
+    tracing::warn!(%user, "Sign-in reported as not performed by the user");
+    notifications::notify_admins(
+        &tx,
+        Severity::Critical,
+        "Sign-in reported as suspicious",
+        &format!("User {user} reported a sign-in they did not perform; the session was revoked and a password reset is required"),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse(()))
+}
+
 #[instrument(skip_all, name = "api_login_request_handler")]
 async fn api_login(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     ApiJson(payload): ApiJson<LoginPayload>,
 ) -> AppResult<ApiResponse<String>> {
-    let conn = state.conn().await?;
-    handle_login(&conn, payload).await
+    let client = LoginClient::new(&state, peer, &headers);
+    let mut conn = state.conn().await?;
+    let tx = conn.transaction().await?;
+    let v = handle_login(&tx, payload, client).await?;
+    tx.commit().await?;
+    Ok(v)
 }
 
 #[instrument(skip_all, name = "browser_login_request_handler")]
 async fn browser_login(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     ApiJson(payload): ApiJson<LoginPayload>,
 ) -> AppResult<Response> {
-    let conn = state.conn().await?;
-    let v = handle_login(&conn, payload).await?;
-    Ok((make_cookies(v.0), ApiResponse(())).into_response())
+    let client = LoginClient::new(&state, peer, &headers);
+    let mut conn = state.conn().await?;
+    let tx = conn.transaction().await?;
+    let v = handle_login(&tx, payload, client).await?;
+    tx.commit().await?;
+    Ok((
+        make_cookies(v.0, state.config().cookie_path()),
+        ApiResponse(()),
+    )
+        .into_response())
 }
 
-fn make_cookies(token: String) -> CookieJar {
+fn make_cookies(token: String, path: &str) -> CookieJar {
+    make_cookie(SESSION_COOKIE, token, path)
+}
+
+fn make_cookie(name: &'static str, token: String, path: &str) -> CookieJar {
     let jar = CookieJar::new();
-    let mut cookie = Cookie::new(SESSION_COOKIE, token);
+    let mut cookie = Cookie::new(name, token);
     cookie.set_http_only(true);
-    cookie.set_path("/");
+    cookie.set_path(path.to_string());
     cookie.set_secure(false);
     cookie.set_same_site(SameSite::None);
     jar.add(cookie)
 }
 
+fn removal_cookie(name: &'static str, path: &str) -> Cookie<'static> {
+    let mut cookie = Cookie::named(name);
+    cookie.set_path(path.to_string());
+    cookie
+}
+
 #[instrument(skip_all, name = "register_request_handler")]
 async fn register(
     State(state): State<AppState>,
     ApiJson(payload): ApiJson<RegisterPayload>,
 ) -> AppResult<ApiResponse<()>> {
+    check_password_policy(
+        &state.config().password,
+        &payload.password,
+        Some(&payload.user),
+    )?;
+    state.breach().ensure_safe(&payload.password).await?;
     let hashed =
         tokio::task::spawn_blocking(move || hash_password(payload.password.as_bytes())).await??;
     let mut conn = state.conn().await?;
@@ -150,11 +346,13 @@ async fn logout(State(state): State<AppState>, parts: Parts) -> AppResult<Respon
     let value = session.value();
     let conn = state.conn().await?;
     let stmt = conn
-        .prepare_cached("delete from sessions where token = $1")
+        .prepare_cached("delete from sessions where token = $1 returning id")
         .await?;
-    conn.execute(&stmt, &[&value]).await?;
+    if let Some(row) = conn.query_opt(&stmt, &[&value]).await? {
+        state.sessions().invalidate(&row.get("id"));
+    }
     Ok((
-        cookies.remove(Cookie::named(SESSION_COOKIE)),
+        cookies.remove(removal_cookie(SESSION_COOKIE, state.config().cookie_path())),
         ApiResponse(()),
     )
         .into_response())
@@ -164,16 +362,150 @@ async fn logout(State(state): State<AppState>, parts: Parts) -> AppResult<Respon
 async fn refresh(
     State(state): State<AppState>,
     CookieAuth(info): CookieAuth,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    parts: Parts,
 ) -> AppResult<ApiResponse<String>> {
     let conn = state.conn().await?;
     let stmt = conn
         .prepare_cached("select roles from users where id = $1")
         .await?;
     let row = conn.query_one(&stmt, &[&info.user]).await?;
+    let admin = match CookieJar::from_headers(&parts.headers).get(ADMIN_SESSION_COOKIE) {
+        Some(cookie) if state.config().admin.session => {
+            let address = client_ip(peer.ip(), &parts.headers, &state.config().trusted_proxies);
+            admin_session_valid(&conn, &state, &info.id, cookie.value(), address).await?
+        }
+        _ => false,
+    };
     let authentra = AuthentraClaims {
         roles: row.get("roles"),
     };
-    let claims = Claims::new(info.user, info.id, authentra);
+    let issuer = state.auth().issuer();
+    let claims = if admin {
+        Claims::admin(issuer, info.user, info.id, authentra)
+    } else {
+        Claims::new(issuer, info.user, info.id, authentra)
+    };
     let token = jsonwebtoken::encode(&jwt_header(), &claims, state.auth().encoding())?;
     Ok(ApiResponse(token))
 }
+
+#[instrument(skip_all, name = "admin_session_valid")]
+async fn admin_session_valid(
+    conn: &impl GenericClient,
+    state: &AppState,
+    session: &Uuid,
+    token: &str,
+    address: IpAddr,
+) -> AppResult<bool> {
+    let config = &state.config().admin;
+    let address = config.bind.then_some(address);
+    let stmt = conn
+        .prepare_cached("update admin_sessions set last_used = now() where token = $1 and session = $2 and creation_time > now() - make_interval(secs => $3) and last_used > now() - make_interval(secs => $4) and ($5::inet is null or address = $5) returning id")
+        .await?;
+    let row = conn
+        .query_opt(
+            &stmt,
+            &[
+                &token,
+                session,
+                &(config.lifetime as f64),
+                &(config.idle as f64),
+                &address,
+            ],
+        )
+        .await?;
+    Ok(row.is_some())
+}
+
+#[derive(Deserialize)]
+struct ElevatePayload {
+    password: String,
+}
+
+#[instrument(skip_all, name = "admin_elevate_handler")]
+async fn elevate(
+    State(state): State<AppState>,
+    CookieAuth(info): CookieAuth,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    ApiJson(payload): ApiJson<ElevatePayload>,
+) -> AppResult<Response> {
+    if !state.config().admin.session {
+        return Err(
+            ApiError::new(StatusCode::BAD_REQUEST, "Admin sessions are not enabled").into(),
+        );
+    }
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached("select roles,password from users where id = $1 and active")
+        .await?;
+    let Some(row) = conn.query_opt(&stmt, &[&info.user]).await? else { return Err(AuthError::InvalidSession.into()) };
+    let roles: Vec<UserRole> = row.get("roles");
+    if !roles.contains(&UserRole::Admin) {
+        return Err(ErrorKind::forbidden().into());
+    }
+    let Some(password): Option<String> = row.get("password") else { return Err(AuthError::InvalidCredentials.into()) };
+    let passed = tokio::task::spawn_blocking(move || {
+        handle_result(verify_password(
+            password.as_str(),
+            payload.password.as_bytes(),
+        ))
+    })
+    .await??;
+    if passed.is_none() {
+        tracing::warn!(user = %info.user, "Admin elevation with invalid password");
+        notifications::notify_admins(
+            &conn,
+            Severity::Warning,
+            "Failed admin elevation",
+            &format!("User {} failed to elevate to an admin session", info.user),
+        )
+        .await?;
+        return Err(AuthError::InvalidCredentials.into());
+    }
+    let token = {
+        let mut rng = thread_rng();
+        Alphanumeric.sample_string(&mut rng, 255)
+    };
+    let address = client_ip(peer.ip(), &headers, &state.config().trusted_proxies);
+    let stmt = conn
+        .prepare_cached("insert into admin_sessions(session,token,address) values($1,$2,$3)")
+        .await?;
+    conn.execute(&stmt, &[&info.id, &token, &address]).await?;
+    tracing::warn!(user = %info.user, %address, "Admin session started");
+    notifications::notify_admins(
+        &conn,
+        Severity::Info,
+        "Admin session started",
+        &format!(
+            "User {} elevated to an admin session from {address}",
+            info.user
+        ),
+    )
+    .await?;
+    Ok((
+        make_cookie(ADMIN_SESSION_COOKIE, token, state.config().cookie_path()),
+        ApiResponse(()),
+    )
+        .into_response())
+}
+
+#[instrument(skip_all, name = "admin_drop_elevation_handler")]
+async fn drop_elevation(State(state): State<AppState>, parts: Parts) -> AppResult<Response> {
+    let cookies = CookieJar::from_headers(&parts.headers);
+    let Some(cookie) = cookies.get(ADMIN_SESSION_COOKIE) else { return Ok(().into_response()) };
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached("delete from admin_sessions where token = $1")
+        .await?;
+    conn.execute(&stmt, &[&cookie.value()]).await?;
+    Ok((
+        cookies.remove(removal_cookie(
+            ADMIN_SESSION_COOKIE,
+            state.config().cookie_path(),
+        )),
+        ApiResponse(()),
+    )
+        .into_response())
+}
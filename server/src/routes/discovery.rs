@@ -0,0 +1,41 @@
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+
+use crate::{error::ErrorKind, routes::InternalScope, AppResult, AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/openid-configuration", get(openid_configuration))
+}
+
+#[derive(Serialize)]
+struct OpenIdConfiguration {
+    issuer: String,
+    authorization_endpoint: String,
+    response_types_supported: Vec<&'static str>,
+    response_modes_supported: Vec<&'static str>,
+    subject_types_supported: Vec<&'static str>,
+    scopes_supported: Vec<&'static str>,
+    code_challenge_methods_supported: Vec<&'static str>,
+    claims_parameter_supported: bool,
+}
+
+async fn openid_configuration(
+    State(state): State<AppState>,
+) -> AppResult<Json<OpenIdConfiguration>> {
+    if state.config().external_url.is_none() {
+        return Err(ErrorKind::not_found().into());
+    }
+    let issuer = state.auth().issuer().to_string();
+    let mut scopes_supported = vec!["openid", "email"];
+    scopes_supported.extend(InternalScope::string_values());
+    Ok(Json(OpenIdConfiguration {
+        authorization_endpoint: format!("{issuer}/oauth/authorize"),
+        issuer,
+        response_types_supported: vec!["code"],
+        response_modes_supported: vec!["query"],
+        subject_types_supported: vec!["public"],
+        scopes_supported,
+        code_challenge_methods_supported: vec!["S256"],
+        claims_parameter_supported: true,
+    }))
+}
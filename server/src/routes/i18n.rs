@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Router,
+};
+use tracing::instrument;
+
+use crate::{
+    error::ApiError,
+    i18n::{is_valid_locale, translations},
+    ApiResponse, AppResult, AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/:locale", get(strings))
+}
+
+#[instrument(skip_all, name = "i18n_strings", fields(locale = %locale))]
+async fn strings(
+    State(state): State<AppState>,
+    Path(locale): Path<String>,
+) -> AppResult<ApiResponse<HashMap<String, String>>> {
+    if !is_valid_locale(&locale) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid locale '{locale}'"),
+        )
+        .into());
+    }
+    let conn = state.conn().await?;
+    Ok(ApiResponse(translations(&conn, &locale).await?))
+}
@@ -0,0 +1,118 @@
+use std::net::IpAddr;
+
+use deadpool_postgres::GenericClient;
+use serde::Serialize;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    config::PageLimits,
+    routes::pagination::{Page, Pagination},
+    AppResult,
+};
+
+#[derive(Serialize)]
+pub struct LoginRecord {
+    id: Uuid,
+    time: i64,
+    address: Option<IpAddr>,
+    user_agent: Option<String>,
+    application: Option<Uuid>,
+    factors: Vec<String>,
+}
+
+pub struct NewLogin<'a> {
+    pub user: &'a Uuid,
+    pub address: &'a IpAddr,
+    pub user_agent: &'a str,
+    pub application: Option<&'a Uuid>,
+    pub device: Option<&'a Uuid>,
+    pub factors: &'a [&'a str],
+}
+
+#[instrument(skip_all, name = "has_login_history")]
+pub async fn has_history(client: &impl GenericClient, user: &Uuid) -> AppResult<bool> {
+    let stmt = client
+        .prepare_cached("select exists (select id from login_history where user_id = $1)")
+        .await?;
+    Ok(client.query_one(&stmt, &[user]).await?.get(0))
+}
+
+#[instrument(skip_all, name = "record_login")]
+pub async fn record(
+    client: &impl GenericClient,
+    login: NewLogin<'_>,
+    retention: u64,
+) -> AppResult<()> {
+    let stmt = client
+        .prepare_cached(
+            "insert into login_history(user_id,address,user_agent,application,device,factors) values($1,$2,$3,$4,$5,$6)",
+        )
+        .await?;
+    client
+        .execute(
+            &stmt,
+            &[
+                login.user,
+                login.address,
+                &login.user_agent,
+                &login.application,
+                &login.device,
+                &login.factors,
+            ],
+        )
+        .await?;
+    let stmt = client
+        .prepare_cached(
+            "delete from login_history where user_id = $1 and time < now() - make_interval(secs => $2)",
+        )
+        .await?;
+    client
+        .execute(&stmt, &[login.user, &(retention as f64)])
+        .await?;
+    Ok(())
+}
+
+#[instrument(skip_all, name = "purge_login_history")]
+pub async fn purge(client: &impl GenericClient, retention: u64) -> AppResult<u64> {
+    let stmt = client
+        .prepare_cached("delete from login_history where time < now() - make_interval(secs => $1)")
+        .await?;
+    Ok(client.execute(&stmt, &[&(retention as f64)]).await?)
+}
+
+#[instrument(skip_all, name = "list_login_history")]
+pub async fn list(
+    client: &impl GenericClient,
+    user: &Uuid,
+    pagination: &Pagination,
+    limits: PageLimits,
+) -> AppResult<Page<LoginRecord>> {
+    let stmt = client
+        .prepare_cached("select count(*) from login_history where user_id = $1")
+        .await?;
+    let total: i64 = client.query_one(&stmt, &[user]).await?.get(0);
+    let stmt = client
+        .prepare_cached(
+            "select id,extract(epoch from time)::int8 as time,address,user_agent,application,factors from login_history where user_id = $1 order by time desc limit $2 offset $3",
+        )
+        .await?;
+    let rows = client
+        .query(
+            &stmt,
+            &[user, &pagination.limit(limits), &pagination.offset(limits)],
+        )
+        .await?;
+    let records = rows
+        .into_iter()
+        .map(|row| LoginRecord {
+            id: row.get("id"),
+            time: row.get("time"),
+            address: row.get("address"),
+            user_agent: row.get("user_agent"),
+            application: row.get("application"),
+            factors: row.get("factors"),
+        })
+        .collect();
+    Ok(Page::from_offset(records, total, pagination, limits))
+}
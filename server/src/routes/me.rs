@@ -0,0 +1,303 @@
+use axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, DefaultBodyLimit, Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    routing::{delete, get, post, put},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    auth::ApiAuth,
+    error::{ApiError, ErrorKind},
+    routes::{
+        login_history::{self, LoginRecord},
+        notifications::{self, Notification, NotificationFilters},
+        pagination::{Page, Pagination},
+        password,
+    },
+    utils::password::{check_password_policy, handle_result, hash_password, verify_password},
+    ApiJson, ApiQuery, ApiResponse, AppResult, AppState,
+};
+
+pub fn router(media_size: usize) -> Router<AppState> {
+    Router::new()
+        .route("/consents", get(consents))
+        .route("/consents/:id", delete(revoke_consent))
+        .route("/logins", get(logins))
+        .route("/notifications", get(list_notifications))
+        .route("/notifications/read", post(read_all_notifications))
+        .route("/notifications/:id/read", post(read_notification))
+        .route("/password", put(change_password))
+        .route(
+            "/avatar",
+            get(avatar)
+                .put(upload_avatar)
+                .delete(delete_avatar)
+                .layer(DefaultBodyLimit::max(media_size)),
+        )
+}
+
+#[derive(Serialize)]
+struct ConsentApplication {
+    id: Uuid,
+    name: String,
+    logo_uri: Option<String>,
+    publisher: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EncodedConsent {
+    application: ConsentApplication,
+    scopes: Vec<String>,
+    implicit: bool,
+}
+
+#[instrument(skip_all, name = "me_consents")]
+async fn consents(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+) -> AppResult<ApiResponse<Vec<EncodedConsent>>> {
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached(
+            "select a.id,a.name,a.logo_uri,a.publisher,c.scopes,c.implicit from consents c join applications a on a.id = c.application where c.user_id = $1 and c.given order by a.name",
+        )
+        .await?;
+    let rows = conn.query(&stmt, &[&auth.user]).await?;
+    Ok(ApiResponse(
+        rows.into_iter()
+            .map(|row| EncodedConsent {
+                application: ConsentApplication {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    logo_uri: row.get("logo_uri"),
+                    publisher: row.get("publisher"),
+                },
+                scopes: row.get("scopes"),
+                implicit: row.get("implicit"),
+            })
+            .collect(),
+    ))
+}
+
+#[instrument(skip_all, name = "me_revoke_consent")]
+async fn revoke_consent(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    Path(id): Path<Uuid>,
+) -> AppResult<ApiResponse<()>> {
+    let mut conn = state.conn().await?;
+    let tx = conn.transaction().await?;
+    let stmt = tx
+        .prepare_cached("delete from consents where user_id = $1 and application = $2")
+        .await?;
+    let rows = tx.execute(&stmt, &[&auth.user, &id]).await?;
+    if rows == 0 {
+        return Err(ErrorKind::not_found().into());
+    }
+    let stmt = tx
+        .prepare_cached("delete from oauth_sessions where user_id = $1 and application = $2")
+        .await?;
+    tx.execute(&stmt, &[&auth.user, &id]).await?;
+    tx.commit().await?;
+    Ok(ApiResponse(()))
+}
+
+#[instrument(skip_all, name = "me_logins")]
+async fn logins(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    pagination: Pagination,
+) -> AppResult<ApiResponse<Page<LoginRecord>>> {
+    let limits = state.config().page.limits("logins");
+    let conn = state.conn().await?;
+    Ok(ApiResponse(
+        login_history::list(&conn, &auth.user, &pagination, limits).await?,
+    ))
+}
+
+#[instrument(skip_all, name = "me_notifications")]
+async fn list_notifications(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    pagination: Pagination,
+    ApiQuery(filters): ApiQuery<NotificationFilters>,
+) -> AppResult<ApiResponse<Page<Notification>>> {
+    let limits = state.config().page.limits("notifications");
+    let conn = state.conn().await?;
+    Ok(ApiResponse(
+        notifications::list(&conn, &auth.user, filters, &pagination, limits).await?,
+    ))
+}
+
+#[instrument(skip_all, name = "me_read_notification")]
+async fn read_notification(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    Path(id): Path<Uuid>,
+) -> AppResult<ApiResponse<()>> {
+    let conn = state.conn().await?;
+    if notifications::mark_read(&conn, &auth.user, Some(&id)).await? == 0 {
+        return Err(ErrorKind::not_found().into());
+    }
+    Ok(ApiResponse(()))
+}
+
+#[instrument(skip_all, name = "me_read_all_notifications")]
+async fn read_all_notifications(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+) -> AppResult<ApiResponse<()>> {
+    let conn = state.conn().await?;
+    notifications::mark_read(&conn, &auth.user, None).await?;
+    Ok(ApiResponse(()))
+}
+
+#[derive(Deserialize)]
+struct PasswordPayload {
+    current: String,
+    new: String,
+}
+
+#[instrument(skip_all, name = "me_change_password")]
+async fn change_password(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    ApiJson(payload): ApiJson<PasswordPayload>,
+) -> AppResult<ApiResponse<()>> {
+    let mut conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached("select name,password from users where id = $1")
+        .await?;
+    let row = conn.query_one(&stmt, &[&auth.user]).await?;
+    let name: String = row.get("name");
+    if let Some(current) = row.get::<_, Option<String>>("password") {
+        let passed = tokio::task::spawn_blocking(move || {
+            handle_result(verify_password(
+                current.as_str(),
+                payload.current.as_bytes(),
+            ))
+        })
+        .await??;
+        if passed.is_none() {
+            return Err(ApiError::new(StatusCode::FORBIDDEN, "Invalid current password").into());
+        }
+    }
+    check_password_policy(&state.config().password, &payload.new, Some(&name))?;
+    state.breach().ensure_safe(&payload.new).await?;
+    let hashed =
+        tokio::task::spawn_blocking(move || hash_password(payload.new.as_bytes())).await??;
+    let tx = conn.transaction().await?;
+    password::change_password(
+        &tx,
+        state.sessions(),
+        &auth.user,
+        &auth.user,
+        &hashed,
+        Some(&auth.id),
+        state.config().password.revoke,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse(()))
+}
+
+#[derive(Serialize)]
+struct EncodedAvatar {
+    url: Option<String>,
+}
+
+#[instrument(skip_all, name = "me_avatar")]
+async fn avatar(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+) -> AppResult<ApiResponse<EncodedAvatar>> {
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached("select avatar,email from users where id = $1")
+        .await?;
+    let row = conn.query_one(&stmt, &[&auth.user]).await?;
+    let url = state.media().avatar_url(
+        state.auth(),
+        row.get::<_, Option<&str>>("avatar"),
+        row.get::<_, Option<&str>>("email"),
+    )?;
+    Ok(ApiResponse(EncodedAvatar { url }))
+}
+
+#[instrument(skip_all, name = "me_upload_avatar")]
+async fn upload_avatar(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> AppResult<ApiResponse<EncodedAvatar>> {
+    let body = body.map_err(ErrorKind::from)?;
+    let Some(content_type) = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) else { return Err(ApiError::new(StatusCode::BAD_REQUEST, "Missing content type").into()) };
+    let key = state.media().put(content_type, &body).await?;
+    let mut upload = UnsavedAvatar {
+        state: state.clone(),
+        key: Some(key.clone()),
+    };
+    let mut conn = state.conn().await?;
+    let tx = conn.transaction().await?;
+    let stmt = tx
+        .prepare_cached(
+            "update users u set avatar = $2 from users old where u.id = old.id and u.id = $1 returning old.avatar",
+        )
+        .await?;
+    let previous: Option<String> = tx
+        .query_one(&stmt, &[&auth.user, &key])
+        .await?
+        .get("avatar");
+    tx.commit().await?;
+    upload.key = None;
+    if let Some(previous) = previous {
+        remove_avatar_file(&state, &previous).await;
+    }
+    let url = state.media().signed_url(state.auth(), &key)?;
+    Ok(ApiResponse(EncodedAvatar { url: Some(url) }))
+}
+
+#[instrument(skip_all, name = "me_delete_avatar")]
+async fn delete_avatar(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+) -> AppResult<ApiResponse<()>> {
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached(
+            "update users u set avatar = null from users old where u.id = old.id and u.id = $1 returning old.avatar",
+        )
+        .await?;
+    let previous: Option<String> = conn.query_one(&stmt, &[&auth.user]).await?.get("avatar");
+    if let Some(previous) = previous {
+        remove_avatar_file(&state, &previous).await;
+    }
+    Ok(ApiResponse(()))
+}
+
+/// Removes a stored avatar that was written but never saved on the user,
+/// because the update failed or the request was cancelled.
+struct UnsavedAvatar {
+    state: AppState,
+    key: Option<String>,
+}
+
+impl Drop for UnsavedAvatar {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let state = self.state.clone();
+            tokio::spawn(async move { remove_avatar_file(&state, &key).await });
+        }
+    }
+}
+
+async fn remove_avatar_file(state: &AppState, key: &str) {
+    if let Err(err) = state.media().delete(key).await {
+        tracing::warn!("Failed to delete avatar {key}: {err}");
+    }
+}
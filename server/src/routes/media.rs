@@ -0,0 +1,41 @@
+use axum::{
+    extract::{Path, State},
+    http::header::{CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+
+use crate::{error::ErrorKind, ApiQuery, AppResult, AppState};
+
+const MEDIA_CSP: &str = "default-src 'none'; sandbox";
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/:key", get(media))
+}
+
+#[derive(Deserialize)]
+struct SignedQuery {
+    token: String,
+}
+
+async fn media(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    ApiQuery(query): ApiQuery<SignedQuery>,
+) -> AppResult<impl IntoResponse> {
+    let media = state.media();
+    media.verify(state.auth(), &key, &query.token)?;
+    let Some((bytes, content_type)) = media.get(&key).await? else { return Err(ErrorKind::not_found().into()) };
+    let cache_control = format!("private, max-age={}", media.lifetime().as_secs());
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (CACHE_CONTROL, cache_control),
+            (CONTENT_SECURITY_POLICY, MEDIA_CSP.to_string()),
+            (X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        bytes,
+    ))
+}
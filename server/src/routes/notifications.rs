@@ -0,0 +1,144 @@
+use deadpool_postgres::GenericClient;
+use postgres_types::{FromSql, ToSql};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    config::PageLimits,
+    routes::pagination::{Page, Pagination},
+    utils::query::Filter,
+    AppResult,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, FromSql, ToSql, PartialEq, Eq)]
+#[postgres(name = "notification_severity")]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[postgres(name = "info")]
+    Info,
+    #[postgres(name = "warning")]
+    Warning,
+    #[postgres(name = "critical")]
+    Critical,
+}
+
+#[derive(Serialize)]
+pub struct Notification {
+    id: Uuid,
+    severity: Severity,
+    title: String,
+    message: String,
+    time: i64,
+    read: bool,
+}
+
+#[derive(Deserialize)]
+pub struct NotificationFilters {
+    severity: Option<Severity>,
+    unread: Option<bool>,
+}
+
+impl NotificationFilters {
+    fn filter(self, user: Uuid) -> Filter {
+        let mut filter = Filter::new();
+        filter.eq("user_id", user);
+        if let Some(severity) = self.severity {
+            filter.ge("severity", severity);
+        }
+        match self.unread {
+            Some(true) => filter.is_null("read_at"),
+            Some(false) => filter.is_not_null("read_at"),
+            None => {}
+        }
+        filter
+    }
+}
+
+#[instrument(skip_all, name = "notify_admins")]
+pub async fn notify_admins(
+    client: &impl GenericClient,
+    severity: Severity,
+    title: &str,
+    message: &str,
+) -> AppResult<u64> {
+    let stmt = client
+        .prepare_cached(
+            "insert into notifications(user_id,severity,title,message) select id,$1,$2,$3 from users where 'admin' = any(roles) and active",
+        )
+        .await?;
+    Ok(client
+        .execute(&stmt, &[&severity, &title, &message])
+        .await?)
+}
+
+#[instrument(skip_all, name = "notify_user")]
+pub async fn notify_user(
+    client: &impl GenericClient,
+    user: &Uuid,
+    severity: Severity,
+    title: &str,
+    message: &str,
+) -> AppResult<u64> {
+    let stmt = client
+        .prepare_cached(
+            "insert into notifications(user_id,severity,title,message) values($1,$2,$3,$4)",
+        )
+        .await?;
+    Ok(client
+        .execute(&stmt, &[user, &severity, &title, &message])
+        .await?)
+}
+
+#[instrument(skip_all, name = "list_notifications")]
+pub async fn list(
+    client: &impl GenericClient,
+    user: &Uuid,
+    filters: NotificationFilters,
+    pagination: &Pagination,
+    limits: PageLimits,
+) -> AppResult<Page<Notification>> {
+    let mut filter = filters.filter(*user);
+    let stmt = client
+        .prepare_cached(&format!(
+            "select count(*) from notifications{}",
+            filter.where_clause()
+        ))
+        .await?;
+    let total: i64 = client.query_one(&stmt, &filter.params()).await?.get(0);
+    let limit = filter.bind(pagination.limit(limits));
+    let offset = filter.bind(pagination.offset(limits));
+    let stmt = client
+        .prepare_cached(&format!(
+            "select id,severity,title,message,extract(epoch from time)::int8 as time,read_at is not null as read from notifications{} order by time desc limit {limit} offset {offset}",
+            filter.where_clause()
+        ))
+        .await?;
+    let rows = client.query(&stmt, &filter.params()).await?;
+    let notifications = rows
+        .into_iter()
+        .map(|row| Notification {
+            id: row.get("id"),
+            severity: row.get("severity"),
+            title: row.get("title"),
+            message: row.get("message"),
+            time: row.get("time"),
+            read: row.get("read"),
+        })
+        .collect();
+    Ok(Page::from_offset(notifications, total, pagination, limits))
+}
+
+#[instrument(skip_all, name = "mark_notifications_read")]
+pub async fn mark_read(
+    client: &impl GenericClient,
+    user: &Uuid,
+    id: Option<&Uuid>,
+) -> AppResult<u64> {
+    let stmt = client
+        .prepare_cached(
+            "update notifications set read_at = coalesce(read_at, now()) where user_id = $1 and ($2::uuid is null or id = $2)",
+        )
+        .await?;
+    Ok(client.execute(&stmt, &[user, &id]).await?)
+}
@@ -7,21 +7,34 @@ use axum::{
     routing::get,
     Json, Router,
 };
+use deadpool_postgres::GenericClient;
 use derive_more::Display;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_with::{formats::SpaceSeparator, serde_as, StringWithSeparator};
+use tokio_postgres::Row;
+use tracing::instrument;
 use url::Url;
 use uuid::Uuid;
 
 use crate::{
     auth::ApiAuth,
     error::{Error, ErrorKind, IntoError},
+    features::Feature,
+    telemetry::decision::Decision,
     ApiResponse, AppResult, AppState,
 };
 
-use super::InternalScope;
+use super::{ApplicationKind, ConsentMode, InternalScope};
+
+use self::{
+    claims::ClaimsRequest,
+    consent::{consent_grant, consent_screen, locales, store_consent, ConsentScreen},
+};
+
+mod claims;
+mod consent;
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -52,10 +65,74 @@ pub struct OAuthAuthorizeParameters {
     #[serde_as(as = "StringWithSeparator::<SpaceSeparator, String>")]
     pub scopes: Vec<String>,
     pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<CodeChallengeMethod>,
+    pub claims: Option<String>,
     #[serde(flatten)]
     pub other: HashMap<String, String>,
 }
 
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+pub enum CodeChallengeMethod {
+    #[serde(rename = "plain")]
+    Plain,
+    #[serde(rename = "S256")]
+    S256,
+}
+
+impl CodeChallengeMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CodeChallengeMethod::Plain => "plain",
+            CodeChallengeMethod::S256 => "S256",
+        }
+    }
+}
+
+fn is_loopback(url: &Url) -> bool {
+    match url.host() {
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        _ => false,
+    }
+}
+
+pub fn is_valid_redirect_uri(kind: &ApplicationKind, uri: &str) -> bool {
+    let Ok(url) = Url::from_str(uri) else { return false };
+    if url.fragment().is_some() {
+        return false;
+    }
+    match url.scheme() {
+        "https" => true,
+        "http" => *kind != ApplicationKind::Native || is_loopback(&url),
+        scheme => *kind == ApplicationKind::Native && scheme.contains('.'),
+    }
+}
+
+pub fn redirect_uri_matches(
+    kind: &ApplicationKind,
+    registered: &[String],
+    raw: &str,
+    requested: &Url,
+) -> bool {
+    if registered.iter().any(|uri| uri == raw) {
+        return true;
+    }
+    if *kind != ApplicationKind::Native || requested.scheme() != "http" || !is_loopback(requested) {
+        return false;
+    }
+    let mut requested = requested.clone();
+    let _ = requested.set_port(None);
+    registered
+        .iter()
+        .filter_map(|uri| Url::from_str(uri).ok())
+        .filter(is_loopback)
+        .any(|mut uri| {
+            let _ = uri.set_port(None);
+            uri == requested
+        })
+}
+
 #[serde_as]
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub struct ConsentInfo {
@@ -81,6 +158,7 @@ pub struct NewError {
     pub error_uri: Option<String>,
     #[serde(skip)]
     pub redirect_uri: Option<Url>,
+    pub correlation_id: Uuid,
 }
 
 #[derive(Serialize)]
@@ -108,6 +186,11 @@ impl IntoResponse for NewError {
 
             Redirect::temporary(redirect_uri.as_str()).into_response()
         } else {
+            tracing::warn!(
+                correlation_id = %self.correlation_id,
+                error = ?self.kind,
+                "OAuth request failed without redirect"
+            );
             let status = self.kind.status();
             (status, Json(self)).into_response()
         }
@@ -171,6 +254,7 @@ impl NewError {
             kind,
             error_uri,
             redirect_uri,
+            correlation_id: Uuid::new_v4(),
         }
     }
     pub fn invalid_client(
@@ -395,22 +479,35 @@ pub async fn authorize_request(
     method: Method,
     OAuthQuery(parameters): OAuthQuery<OAuthAuthorizeParameters>,
 ) -> AppResult<Response> {
+    let mut decision = Decision::new("authorize");
+    decision.subject(auth.user);
     let conn = state.conn().await?;
     let stmt = conn
         .prepare_cached("select * from applications where client_id = $1")
         .await?;
-    let application = conn
-        .query_opt(&stmt, &[&parameters.client_id])
-        .await?
-        .ok_or_else(|| NewError::invalid_client(None, None, None).into_error())?;
-    let uri = Url::from_str(&parameters.redirect_uri)
-        .map_err(|_| NewError::invalid_redirect_uri(None, None, None).into_error())?;
-    {
-        let uris: Vec<String> = application.get("redirect_uri");
-        if !uris.contains(&parameters.redirect_uri) {
-            return Err(NewError::invalid_redirect_uri(None, None, None).into());
-        }
+    let application = conn.query_opt(&stmt, &[&parameters.client_id]).await?;
+    let Some(application) = application else {
+        decision.step("client", false);
+        decision.deny("unknown_client");
+        return Err(NewError::invalid_client(None, None, None).into());
+    };
+    let kind: ApplicationKind = application.get("kind");
+    let client_enabled =
+        kind != ApplicationKind::Native || state.feature_enabled(Feature::NativeApplications);
+    if !decision.step("client", client_enabled) {
+        decision.deny("native_applications_disabled");
+        return Err(NewError::invalid_client(None, None, None).into());
     }
+    let uris: Vec<String> = application.get("redirect_uri");
+    let uri = Url::from_str(&parameters.redirect_uri)
+        .ok()
+        .filter(|uri| redirect_uri_matches(&kind, &uris, &parameters.redirect_uri, uri));
+    let Some(uri) = uri else {
+        decision.step("redirect_uri", false);
+        decision.deny("invalid_redirect_uri");
+        return Err(NewError::invalid_redirect_uri(None, None, None).into());
+    };
+    decision.step("redirect_uri", true);
     // let client_secret: Option<String> = application.get("client_secret");
     // if let Some(client_secret) = client_secret {
     //     if let Some(secret) = parameters.client_secret {
@@ -421,22 +518,56 @@ pub async fn authorize_request(
     //         return Err(OAuthError::InvalidClientSecret.into());
     //     }
     // }
-    match parameters.response_type {
-        ResponseType::Code => {}
-        _ => {
-            return Err(NewError::authorize_unsupported_response_type(
-                None,
-                parameters.state,
-                None,
-                Some(uri),
-            )
-            .into())
-        }
+    if !decision.step(
+        "response_type",
+        matches!(parameters.response_type, ResponseType::Code),
+    ) {
+        decision.deny("unsupported_response_type");
+        return Err(NewError::authorize_unsupported_response_type(
+            None,
+            parameters.state,
+            None,
+            Some(uri),
+        )
+        .into());
     }
-    match parameters.response_mode {
-        ResponseMode::Query => {}
-        // ResponseMode::Fragment => {}
-        _ => return Err(NewError::invalid_request(None, parameters.state, None, Some(uri)).into()),
+    if kind == ApplicationKind::Native
+        && !decision.step(
+            "pkce",
+            parameters.code_challenge.is_some()
+                && parameters.code_challenge_method == Some(CodeChallengeMethod::S256),
+        )
+    {
+        decision.deny("pkce_required");
+        return Err(NewError::invalid_request(
+            Some("PKCE with S256 is required for native applications".into()),
+            parameters.state,
+            None,
+            Some(uri),
+        )
+        .into());
+    }
+    if parameters.code_challenge.is_some()
+        && !decision.step(
+            "pkce_method",
+            parameters.code_challenge_method == Some(CodeChallengeMethod::S256),
+        )
+    {
+        decision.deny("unsupported_code_challenge_method");
+        return Err(NewError::invalid_request(
+            Some("Only the S256 code challenge method is supported".into()),
+            parameters.state,
+            None,
+            Some(uri),
+        )
+        .into());
+    }
+    if !decision.step(
+        "response_mode",
+        matches!(parameters.response_mode, ResponseMode::Query),
+    ) {
+        decision.deny("unsupported_response_mode");
+        return Err(NewError::invalid_request(None, parameters.state, None, Some(uri)).into());
     }
     let stmt = conn
         .prepare_cached("select * from application_groups where id = $1")
@@ -445,24 +576,99 @@ pub async fn authorize_request(
         .query_one(&stmt, &[&application.get::<_, String>("application_group")])
         .await?;
     let application_internal_scopes: Vec<InternalScope> = application_group.get("scopes");
-    let (scopes, scope_errors) = find_scopes(
+    let found = find_scopes(
         parameters.scopes.clone().into_iter(),
         &application_internal_scopes,
-    )
-    .map_err(|_| {
-        NewError::invalid_scope(None, parameters.state.clone(), None, Some(uri.clone()))
-    })?;
+    );
+    let Ok((scopes, scope_errors)) = found else {
+        decision.step("scope", false);
+        decision.deny("invalid_scope");
+        return Err(NewError::invalid_scope(None, parameters.state, None, Some(uri)).into());
+    };
+    decision.step("scope", true);
+    let requested_claims = match parameters.claims.as_deref().map(ClaimsRequest::parse) {
+        Some(Ok(claims)) => Some(claims),
+        Some(Err(err)) => {
+            decision.step("claims", false);
+            decision.deny("invalid_claims");
+            return Err(NewError::invalid_request(
+                Some(format!("Invalid claims parameter: {err}")),
+                parameters.state,
+                None,
+                Some(uri),
+            )
+            .into());
+        }
+        None => None,
+    };
     match method {
         Method::GET => {
-            return Ok(ApiResponse(OAuthResponse::Get {
-                app_name: application.get("name"),
-                invalid_scopes: scope_errors,
-                scopes: scopes.iter().map(ToString::to_string).collect(),
-            })
-            .into_response());
+            let consent_mode: ConsentMode = application.get("consent_mode");
+            let allow_implicit: bool = application_group.get("allow_implicit_consent");
+            if consent_mode == ConsentMode::Implicit && allow_implicit {
+                decision.step("implicit_consent", true);
+                tracing::info!(
+                    application = %application.get::<_, Uuid>("id"),
+                    user = %auth.user,
+                    "Skipping consent screen for implicit consent application"
+                );
+                let result = issue_code(
+                    &conn,
+                    &auth.user,
+                    &application,
+                    parameters,
+                    uri,
+                    Grant {
+                        scopes,
+                        declined: Vec::new(),
+                        claims: requested_claims,
+                        implicit: true,
+                    },
+                )
+                .await;
+                return conclude(decision, result);
+            }
+            let grant = consent_grant(&conn, &auth.user, &application.get("id")).await?;
+            let all_decided = scopes.iter().all(|s| grant.is_decided(&s.to_string()));
+            let any_granted = scopes.iter().any(|s| grant.scopes.contains(&s.to_string()));
+            // If every requested scope was declined, ask again rather than
+            // issuing a code without any scope.
+            if all_decided && any_granted {
+                decision.step("previous_consent", true);
+                tracing::info!(
+                    application = %application.get::<_, Uuid>("id"),
+                    user = %auth.user,
+                    "Skipping consent screen, all requested scopes were previously decided"
+                );
+                let granted = scopes
+                    .into_iter()
+                    .filter(|s| grant.scopes.contains(&s.to_string()))
+                    .collect();
+                let result = issue_code(
+                    &conn,
+                    &auth.user,
+                    &application,
+                    parameters,
+                    uri,
+                    Grant {
+                        scopes: granted,
+                        declined: Vec::new(),
+                        claims: requested_claims,
+                        implicit: false,
+                    },
+                )
+                .await;
+                return conclude(decision, result);
+            }
+            let locales = locales(parameters.other.get("ui_locales"));
+            let screen =
+                consent_screen(&conn, grant, &application, &scopes, scope_errors, &locales).await?;
+            decision.step("previous_consent", false);
+            decision.prompt("consent_required");
+            return Ok(ApiResponse(OAuthResponse::Get(screen)).into_response());
         }
         Method::POST => {
-            let (selected_scopes, _) = find_scopes(
+            let selected_scopes = find_scopes(
                 parameters.other.keys().cloned().filter_map(|s| {
                     if s.starts_with("enable-scope:") {
                         let res = s.replacen("enable-scope:", "", 1);
@@ -476,12 +682,20 @@ pub async fn authorize_request(
                     }
                 }),
                 &application_internal_scopes,
-            )
-            .map_err(|_| {
-                NewError::invalid_scope(None, parameters.state.clone(), None, Some(uri.clone()))
-            })?;
+            );
+            let Ok((selected_scopes, _)) = selected_scopes else {
+                decision.step("selected_scope", false);
+                decision.deny("invalid_scope");
+                return Err(
+                    NewError::invalid_scope(None, parameters.state, None, Some(uri)).into(),
+                );
+            };
+            let (selected_scopes, declined_scopes): (Vec<Scope>, Vec<Scope>) = scopes
+                .into_iter()
+                .partition(|scope| selected_scopes.contains(scope));
             let denied = parameters.other.contains_key("denied");
-            if denied {
+            if !decision.step("consent", !denied) {
+                decision.deny("consent_denied");
                 return Err(NewError::authorize_access_denied(
                     None,
                     parameters.state,
@@ -490,45 +704,111 @@ pub async fn authorize_request(
                 )
                 .into());
             }
-            let selected_scopes: Vec<String> =
-                selected_scopes.into_iter().map(|s| s.to_string()).collect();
-            let stmt = conn
-                .prepare_cached(
-                    "insert into authorization_codes(user_id,application,redirect_uri,scope) values($1,$2,$3,$4) returning code",
-                )
-                .await?;
-            let code: String = conn
-                .query_one(
-                    &stmt,
-                    &[
-                        &auth.user,
-                        &application.get::<_, Uuid>("id"),
-                        &parameters.redirect_uri,
-                        &selected_scopes.join(" "),
-                    ],
-                )
-                .await?
-                .get(0);
-            match parameters.response_mode {
-                ResponseMode::Query => {
-                    let query = encode_query(CodeRedirect {
-                        code: Some(code),
-                        state: parameters.state,
-                    })?;
-                    let mut uri = uri;
-                    uri.set_query(Some(query.as_str()));
-                    return Ok(Redirect::temporary(uri.as_str()).into_response());
-                }
-                ResponseMode::Fragment => todo!(),
-                _ => {
-                    return Err(ErrorKind::internal().into());
-                }
-            }
+            let result = issue_code(
+                &conn,
+                &auth.user,
+                &application,
+                parameters,
+                uri,
+                Grant {
+                    scopes: selected_scopes,
+                    declined: declined_scopes,
+                    claims: requested_claims,
+                    implicit: false,
+                },
+            )
+            .await;
+            conclude(decision, result)
+        }
+        _ => {
+            decision.deny("method_not_allowed");
+            Err(ErrorKind::Status(StatusCode::METHOD_NOT_ALLOWED).into())
         }
-        _ => return Err(ErrorKind::Status(StatusCode::METHOD_NOT_ALLOWED).into()),
     }
 }
 
+fn conclude(decision: Decision, result: AppResult<Response>) -> AppResult<Response> {
+    match &result {
+        Ok(_) => decision.allow(),
+        Err(_) => decision.deny("issue_failed"),
+    }
+    result
+}
+
+#[instrument(skip_all, name = "issue_authorization_code")]
+async fn issue_code(
+    conn: &impl GenericClient,
+    user: &Uuid,
+    application: &Row,
+    parameters: OAuthAuthorizeParameters,
+    uri: Url,
+    grant: Grant,
+) -> AppResult<Response> {
+    let application_id: Uuid = application.get("id");
+    let scopes: Vec<String> = grant.scopes.iter().map(|s| s.to_string()).collect();
+    let declined: Vec<String> = grant.declined.iter().map(|s| s.to_string()).collect();
+    let implicit = grant.implicit;
+    let requested_claims = grant
+        .claims
+        .map(|claims| claims.restrict(&scopes))
+        .filter(|claims| !claims.is_empty())
+        .map(|claims| serde_json::to_string(&claims))
+        .transpose()
+        .map_err(|_| ErrorKind::internal().into_error())?;
+    // The challenge is only stored for now; there is no token endpoint yet to verify it against.
+    let code_challenge_method = parameters
+        .code_challenge
+        .as_ref()
+        .map(|_| CodeChallengeMethod::S256.as_str());
+    store_consent(conn, user, &application_id, &scopes, &declined, implicit).await?;
+    let stmt = conn
+        .prepare_cached(
+            "insert into authorization_codes(user_id,application,redirect_uri,scope,code_challenge,code_challenge_method,claims) values($1,$2,$3,$4,$5,$6,$7) returning code",
+        )
+        .await?;
+    let code: String = conn
+        .query_one(
+            &stmt,
+            &[
+                user,
+                &application_id,
+                &parameters.redirect_uri,
+                &scopes.join(" "),
+                &parameters.code_challenge,
+                &code_challenge_method,
+                &requested_claims,
+            ],
+        )
+        .await?
+        .get(0);
+    match parameters.response_mode {
+        ResponseMode::Query => {
+            let query = encode_query(CodeRedirect {
+                code: Some(code),
+                state: parameters.state,
+            })?;
+            let mut uri = uri;
+            uri.set_query(Some(query.as_str()));
+            Ok(Redirect::temporary(uri.as_str()).into_response())
+        }
+        _ => Err(NewError::invalid_request(
+            Some("Unsupported response mode".into()),
+            parameters.state,
+            None,
+            Some(uri),
+        )
+        .into()),
+    }
+}
+
+/// The scopes and claims a user granted to an application in one authorization.
+struct Grant {
+    scopes: Vec<Scope>,
+    declined: Vec<Scope>,
+    claims: Option<ClaimsRequest>,
+    implicit: bool,
+}
+
 #[derive(Serialize)]
 struct CodeRedirect {
     code: Option<String>,
@@ -546,11 +826,7 @@ fn encode_query<T: Serialize>(t: T) -> AppResult<String> {
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum OAuthResponse {
-    Get {
-        app_name: String,
-        invalid_scopes: Vec<String>,
-        scopes: Vec<String>,
-    },
+    Get(ConsentScreen),
 }
 
 pub fn find_scopes(
@@ -601,3 +877,51 @@ pub struct TokenRefreshToken {
     pub refresh_token: String,
     pub scope: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use url::Url;
+
+    use super::redirect_uri_matches;
+    use crate::routes::ApplicationKind;
+
+    fn matches(kind: ApplicationKind, registered: &str, requested: &str) -> bool {
+        let url = Url::from_str(requested).unwrap();
+        redirect_uri_matches(&kind, &[registered.to_string()], requested, &url)
+    }
+
+    #[test]
+    fn matches_registered_uri_verbatim() {
+        assert!(matches(
+            ApplicationKind::WebServer,
+            "https://app.example.com",
+            "https://app.example.com"
+        ));
+        assert!(matches(
+            ApplicationKind::WebServer,
+            "https://App.example.com:443/cb",
+            "https://App.example.com:443/cb"
+        ));
+        assert!(!matches(
+            ApplicationKind::WebServer,
+            "https://app.example.com/cb",
+            "https://app.example.com/other"
+        ));
+    }
+
+    #[test]
+    fn relaxes_loopback_port_for_native_only() {
+        assert!(matches(
+            ApplicationKind::Native,
+            "http://127.0.0.1/cb",
+            "http://127.0.0.1:51234/cb"
+        ));
+        assert!(!matches(
+            ApplicationKind::WebServer,
+            "http://127.0.0.1/cb",
+            "http://127.0.0.1:51234/cb"
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::routes::InternalScope;
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ClaimsRequest {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub userinfo: HashMap<String, Option<ClaimRequest>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub id_token: HashMap<String, Option<ClaimRequest>>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ClaimRequest {
+    #[serde(default)]
+    pub essential: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<serde_json::Value>>,
+}
+
+pub fn claim_scope(claim: &str) -> Option<Option<InternalScope>> {
+    match claim {
+        "sub" => Some(None),
+        "email" | "email_verified" => Some(Some(InternalScope::Email)),
+        "name" | "preferred_username" => Some(Some(InternalScope::ProfileRead)),
+        _ => None,
+    }
+}
+
+fn permitted(claim: &str, granted_scopes: &[String]) -> bool {
+    match claim_scope(claim) {
+        Some(None) => true,
+        Some(Some(scope)) => granted_scopes.contains(&scope.to_string()),
+        None => false,
+    }
+}
+
+impl ClaimsRequest {
+    pub fn parse(value: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(value)
+    }
+
+    pub fn restrict(mut self, granted_scopes: &[String]) -> Self {
+        self.userinfo
+            .retain(|claim, _| permitted(claim, granted_scopes));
+        self.id_token
+            .retain(|claim, _| permitted(claim, granted_scopes));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.userinfo.is_empty() && self.id_token.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClaimsRequest;
+
+    #[test]
+    fn restricts_to_permitted_claims() {
+        let request = ClaimsRequest::parse(
+            r#"{"userinfo":{"email":{"essential":true},"name":null,"address":null},"id_token":{"sub":null}}"#,
+        )
+        .unwrap();
+        assert!(request.userinfo["email"].as_ref().unwrap().essential);
+        let restricted = request.restrict(&["email".to_string()]);
+        assert!(restricted.userinfo.contains_key("email"));
+        assert!(!restricted.userinfo.contains_key("name"));
+        assert!(!restricted.userinfo.contains_key("address"));
+        assert!(restricted.id_token.contains_key("sub"));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use deadpool_postgres::GenericClient;
+use serde::Serialize;
+use tokio_postgres::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::AppResult;
+
+use super::Scope;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+#[derive(Debug, Serialize)]
+pub struct ConsentScreen {
+    pub application: ConsentApplication,
+    pub scopes: Vec<ConsentScope>,
+    pub previously_granted: Vec<String>,
+    pub previously_declined: Vec<String>,
+    pub invalid_scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConsentApplication {
+    pub name: String,
+    pub logo_uri: Option<String>,
+    pub publisher: Option<String>,
+}
+
+impl ConsentApplication {
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            name: row.get("name"),
+            logo_uri: row.get("logo_uri"),
+            publisher: row.get("publisher"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConsentScope {
+    pub name: String,
+    pub description: Option<String>,
+    pub new: bool,
+}
+
+pub fn locales(ui_locales: Option<&String>) -> Vec<String> {
+    let mut locales: Vec<String> = ui_locales
+        .map(|s| {
+            s.split(' ')
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    if !locales.iter().any(|l| l == DEFAULT_LOCALE) {
+        locales.push(DEFAULT_LOCALE.into());
+    }
+    locales
+}
+
+#[instrument(skip_all, name = "scope_descriptions")]
+pub async fn scope_descriptions(
+    client: &impl GenericClient,
+    scopes: &[String],
+    locales: &[String],
+) -> AppResult<HashMap<String, String>> {
+    let stmt = client
+        .prepare_cached(
+            "select distinct on (scope) scope, description from scope_descriptions where scope = any($1) and locale = any($2) order by scope, array_position($2, locale::text)",
+        )
+        .await?;
+    let rows = client.query(&stmt, &[&scopes, &locales]).await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.get("scope"), row.get("description")))
+        .collect())
+}
+
+#[derive(Debug, Default)]
+pub struct ConsentGrant {
+    pub scopes: Vec<String>,
+    pub declined: Vec<String>,
+}
+
+impl ConsentGrant {
+    pub fn is_decided(&self, scope: &String) -> bool {
+        self.scopes.contains(scope) || self.declined.contains(scope)
+    }
+}
+
+#[instrument(skip_all, name = "consent_grant")]
+pub async fn consent_grant(
+    client: &impl GenericClient,
+    user: &Uuid,
+    application: &Uuid,
+) -> AppResult<ConsentGrant> {
+    let stmt = client
+        .prepare_cached(
+            "select scopes,declined from consents where user_id = $1 and application = $2 and given",
+        )
+        .await?;
+    let row = client.query_opt(&stmt, &[user, application]).await?;
+    Ok(row
+        .map(|row| ConsentGrant {
+            scopes: row.get("scopes"),
+            declined: row.get("declined"),
+        })
+        .unwrap_or_default())
+}
+
+#[instrument(skip_all, name = "store_consent")]
+pub async fn store_consent(
+    client: &impl GenericClient,
+    user: &Uuid,
+    application: &Uuid,
+    scopes: &[String],
+    declined: &[String],
+    implicit: bool,
+) -> AppResult<()> {
+    let stmt = client
+        .prepare_cached(
+            "insert into consents(user_id,application,given,implicit,scopes,declined) values($1,$2,true,$4,$3,$5) on conflict (user_id, application) do update set given = true, implicit = excluded.implicit, scopes = array(select unnest(consents.scopes || excluded.scopes) except select unnest(excluded.declined)), declined = array(select unnest(consents.declined || excluded.declined) except select unnest(excluded.scopes))",
+        )
+        .await?;
+    client
+        .execute(&stmt, &[user, application, &scopes, &implicit, &declined])
+        .await?;
+    Ok(())
+}
+
+pub async fn consent_screen(
+    client: &impl GenericClient,
+    grant: ConsentGrant,
+    application: &Row,
+    scopes: &[Scope],
+    invalid_scopes: Vec<String>,
+    locales: &[String],
+) -> AppResult<ConsentScreen> {
+    let names: Vec<String> = scopes.iter().map(ToString::to_string).collect();
+    let mut descriptions = scope_descriptions(client, &names, locales).await?;
+    let scopes = names
+        .into_iter()
+        .map(|name| ConsentScope {
+            description: descriptions.remove(&name),
+            new: !grant.is_decided(&name),
+            name,
+        })
+        .collect();
+    Ok(ConsentScreen {
+        application: ConsentApplication::from_row(application),
+        scopes,
+        previously_granted: grant.scopes,
+        previously_declined: grant.declined,
+        invalid_scopes,
+    })
+}
@@ -0,0 +1,109 @@
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::PageLimits, error::Error};
+
+fn page_default() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct Pagination {
+    #[serde(default = "page_default")]
+    pub page: u32,
+    pub per_page: Option<u16>,
+    pub cursor: Option<String>,
+}
+
+impl Pagination {
+    pub fn limit(&self, limits: PageLimits) -> i64 {
+        self.per_page.unwrap_or(limits.default).min(limits.max) as i64
+    }
+
+    pub fn offset(&self, limits: PageLimits) -> i64 {
+        self.limit(limits)
+            .saturating_mul(self.page.saturating_sub(1) as i64)
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Pagination {
+    type Rejection = Error;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let query: Pagination = Query::from_request_parts(parts, state).await?.0;
+        Ok(query)
+    }
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn from_offset(
+        items: Vec<T>,
+        total: i64,
+        pagination: &Pagination,
+        limits: PageLimits,
+    ) -> Self {
+        let limit = pagination.limit(limits);
+        let offset = pagination.offset(limits);
+        let next = (offset + (items.len() as i64) < total)
+            .then(|| format!("page={}&per_page={limit}", pagination.page + 1));
+        let prev =
+            (pagination.page > 1).then(|| format!("page={}&per_page={limit}", pagination.page - 1));
+        Self {
+            items,
+            total,
+            next,
+            prev,
+        }
+    }
+
+    /// Replaces the page-number `next` link of an offset page with the cursor
+    /// of its last row, for listings that are ordered by that cursor.
+    pub fn with_cursor(
+        mut self,
+        pagination: &Pagination,
+        limits: PageLimits,
+        cursor: impl Fn(&T) -> String,
+    ) -> Self {
+        if self.next.is_some() {
+            let limit = pagination.limit(limits);
+            self.next = self
+                .items
+                .last()
+                .map(|last| format!("cursor={}&per_page={limit}", cursor(last)));
+        }
+        self
+    }
+
+    pub fn from_cursor(
+        items: Vec<T>,
+        total: i64,
+        pagination: &Pagination,
+        limits: PageLimits,
+        cursor: impl Fn(&T) -> String,
+    ) -> Self {
+        let limit = pagination.limit(limits);
+        let next = match items.last() {
+            Some(last) if items.len() as i64 == limit => {
+                Some(format!("cursor={}&per_page={limit}", cursor(last)))
+            }
+            _ => None,
+        };
+        Self {
+            items,
+            total,
+            next,
+            prev: None,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use axum::{extract::State, routing::post, Router};
+use deadpool_postgres::GenericClient;
+use serde::Deserialize;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    broadcast::{self, CacheEvent},
+    sessions::SessionCache,
+    telemetry::audit,
+    utils::password::{password_strength, PasswordStrength},
+    ApiJson, ApiResponse, AppResult, AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/strength", post(strength))
+}
+
+#[derive(Deserialize)]
+struct StrengthPayload {
+    password: String,
+    user: Option<String>,
+}
+
+async fn strength(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<StrengthPayload>,
+) -> AppResult<ApiResponse<PasswordStrength>> {
+    // The breach check is left to the write paths, so anonymous callers
+    // can't use this endpoint to make outbound breach lookups.
+    Ok(ApiResponse(password_strength(
+        &state.config().password,
+        &payload.password,
+        payload.user.as_deref(),
+    )))
+}
+
+#[instrument(skip_all, name = "change_password")]
+pub async fn change_password(
+    client: &impl GenericClient,
+    sessions: &SessionCache,
+    actor: &Uuid,
+    user: &Uuid,
+    hashed: &str,
+    keep_session: Option<&Uuid>,
+    revoke: bool,
+) -> AppResult<bool> {
+    let stmt = client
+        .prepare_cached(
+            "update users set password = $2, require_password_reset = false, not_before = case when $3 then now() else not_before end where id = $1",
+        )
+        .await?;
+    if client.execute(&stmt, &[user, &hashed, &revoke]).await? == 0 {
+        return Ok(false);
+    }
+    if revoke {
+        let stmt = client
+            .prepare_cached(
+                "update sessions set not_before_exempt = (select not_before from users where id = $1) where id = $2 and user_id = $1",
+            )
+            .await?;
+        client.execute(&stmt, &[user, &keep_session]).await?;
+        let stmt = client
+            .prepare_cached(
+                "delete from sessions where user_id = $1 and id is distinct from $2 returning id",
+            )
+            .await?;
+        let revoked = client.query(&stmt, &[user, &keep_session]).await?;
+        for row in &revoked {
+            let id: Uuid = row.get("id");
+            sessions.invalidate(&id);
+            broadcast::send(client, CacheEvent::Session(id)).await?;
+        }
+        let stmt = client
+            .prepare_cached("delete from oauth_sessions where user_id = $1")
+            .await?;
+        let oauth_sessions = client.execute(&stmt, &[user]).await?;
+        tracing::warn!(
+            %user,
+            sessions = revoked.len(),
+            oauth_sessions,
+            "Revoked sessions after password change"
+        );
+        audit::write(actor, "revoke", "sessions", Some(user), revoked.len());
+        audit::write(
+            actor,
+            "revoke",
+            "oauth_sessions",
+            Some(user),
+            oauth_sessions as usize,
+        );
+    } else {
+        tracing::warn!(%user, "Password changed");
+    }
+    audit::write(actor, "update", "password", Some(user), 1);
+    Ok(true)
+}
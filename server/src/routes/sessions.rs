@@ -0,0 +1,262 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use deadpool_postgres::GenericClient;
+use serde::Deserialize;
+use tracing::{instrument, Instrument};
+use uuid::Uuid;
+
+use crate::{
+    auth::{ApiAuth, UserRole},
+    broadcast::{self, CacheEvent},
+    error::{ApiError, ErrorKind},
+    jobs::{self, JobState, JobStatus},
+    routes::notifications::{self, Severity},
+    utils::{network::Cidr, query::Filter},
+    ApiJson, ApiResponse, AppResult, AppState,
+};
+
+const BATCH_SIZE: i64 = 500;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/revoke", post(revoke))
+        .route("/revoke/:id", get(revoke_status))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RevokeCriteria {
+    user: Option<Uuid>,
+    role: Option<UserRole>,
+    application: Option<Uuid>,
+    created_before: Option<i64>,
+    network: Option<Cidr>,
+}
+
+impl RevokeCriteria {
+    fn is_empty(&self) -> bool {
+        self.user.is_none()
+            && self.role.is_none()
+            && self.application.is_none()
+            && self.created_before.is_none()
+            && self.network.is_none()
+    }
+
+    fn sessions(&self) -> Option<Filter> {
+        if self.application.is_some() {
+            return None;
+        }
+        let mut filter = self.owner_filter();
+        if let Some(created_before) = self.created_before {
+            filter.before_timestamp("creation_time", created_before);
+        }
+        if let Some(network) = self.network {
+            filter.in_network("address", network);
+        }
+        Some(filter)
+    }
+
+    fn oauth_sessions(&self) -> Option<Filter> {
+        if self.created_before.is_some() || self.network.is_some() {
+            return None;
+        }
+        let mut filter = self.owner_filter();
+        if let Some(application) = self.application {
+            filter.eq("application", application);
+        }
+        Some(filter)
+    }
+
+    fn owner_filter(&self) -> Filter {
+        let mut filter = Filter::new();
+        if let Some(user) = self.user {
+            filter.eq("user_id", user);
+        }
+        if let Some(role) = self.role.clone() {
+            filter.user_with_role("user_id", role);
+        }
+        filter
+    }
+}
+
+async fn count(client: &impl GenericClient, table: &str, filter: &Filter) -> AppResult<u64> {
+    let stmt = client
+        .prepare_cached(&format!(
+            "select count(*) from {table}{}",
+            filter.where_clause()
+        ))
+        .await?;
+    let count: i64 = client.query_one(&stmt, &filter.params()).await?.get(0);
+    Ok(count as u64)
+}
+
+async fn delete_batches(
+    state: &AppState,
+    job: &Uuid,
+    table: &str,
+    filter: &Filter,
+) -> AppResult<u64> {
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached(&format!(
+            "delete from {table} where id in (select id from {table}{} limit {BATCH_SIZE})",
+            filter.where_clause()
+        ))
+        .await?;
+    let mut deleted = 0;
+    loop {
+        let batch = conn.execute(&stmt, &filter.params()).await?;
+        if batch == 0 {
+            return Ok(deleted);
+        }
+        deleted += batch;
+        jobs::progress(&conn, job, batch).await?;
+    }
+}
+
+async fn run_revocation(
+    state: &AppState,
+    job: &Uuid,
+    sessions: Option<Filter>,
+    oauth_sessions: Option<Filter>,
+) -> AppResult<String> {
+    let mut revoked_sessions = 0;
+    let mut revoked_oauth = 0;
+    if let Some(filter) = &sessions {
+        revoked_sessions = delete_batches(state, job, "sessions", filter).await?;
+        state.sessions().clear();
+        broadcast::send(&state.conn().await?, CacheEvent::Sessions).await?;
+    }
+    if let Some(filter) = &oauth_sessions {
+        revoked_oauth = delete_batches(state, job, "oauth_sessions", filter).await?;
+    }
+    Ok(format!(
+        "Revoked {revoked_sessions} sessions and {revoked_oauth} oauth sessions"
+    ))
+}
+
+#[instrument(skip_all, name = "revoke_sessions")]
+async fn revoke(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    ApiJson(criteria): ApiJson<RevokeCriteria>,
+) -> AppResult<(StatusCode, ApiResponse<JobStatus>)> {
+    auth.check_admin()?;
+    if criteria.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "At least one revocation criterion is required",
+        )
+        .into());
+    }
+    let sessions = criteria.sessions();
+    let oauth_sessions = criteria.oauth_sessions();
+    if sessions.is_none() && oauth_sessions.is_none() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Criteria do not match any session kind",
+        )
+        .into());
+    }
+    let conn = state.conn().await?;
+    let mut total = 0;
+    if let Some(filter) = &sessions {
+        total += count(&conn, "sessions", filter).await?;
+    }
+    if let Some(filter) = &oauth_sessions {
+        total += count(&conn, "oauth_sessions", filter).await?;
+    }
+    let status = jobs::start(&conn, "revoke-sessions", total).await?;
+    notifications::notify_admins(
+        &conn,
+        Severity::Warning,
+        "Bulk session revocation",
+        &format!(
+            "User {} started revoking {total} sessions matching {criteria:?}",
+            auth.user
+        ),
+    )
+    .await?;
+    drop(conn);
+    let job = status.id;
+    tracing::warn!(user = %auth.user, %job, ?criteria, total, "Bulk session revocation started");
+    let task_state = state.clone();
+    tokio::spawn(
+        async move {
+            let (outcome, message) =
+                match run_revocation(&task_state, &job, sessions, oauth_sessions).await {
+                    Ok(message) => {
+                        tracing::warn!(%job, "{message}");
+                        (JobState::Completed, message)
+                    }
+                    Err(err) => {
+                        tracing::error!(%job, "Bulk session revocation failed: {}", err.kind());
+                        (
+                            JobState::Failed,
+                            "Bulk session revocation failed".to_string(),
+                        )
+                    }
+                };
+            let finished = match task_state.conn().await {
+                Ok(conn) => jobs::finish(&conn, &job, outcome, &message).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = finished {
+                tracing::error!(%job, "Failed to record job result: {}", err.kind());
+            }
+        }
+        .in_current_span(),
+    );
+    Ok((StatusCode::ACCEPTED, ApiResponse(status)))
+}
+
+#[instrument(skip_all, name = "revoke_sessions_status")]
+async fn revoke_status(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    Path(id): Path<Uuid>,
+) -> AppResult<ApiResponse<JobStatus>> {
+    auth.check_admin()?;
+    let conn = state.conn().await?;
+    match jobs::get(&conn, &id).await? {
+        Some(status) => Ok(ApiResponse(status)),
+        None => Err(ErrorKind::not_found().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::RevokeCriteria;
+
+    #[test]
+    fn targets_matching_session_kinds() {
+        let by_user = RevokeCriteria {
+            user: Some(Uuid::nil()),
+            ..Default::default()
+        };
+        assert!(by_user.sessions().is_some());
+        assert!(by_user.oauth_sessions().is_some());
+        let by_application = RevokeCriteria {
+            application: Some(Uuid::nil()),
+            ..Default::default()
+        };
+        assert!(by_application.sessions().is_none());
+        assert!(by_application.oauth_sessions().is_some());
+        let by_age = RevokeCriteria {
+            created_before: Some(0),
+            role: Some(crate::auth::UserRole::Admin),
+            ..Default::default()
+        };
+        let filter = by_age.sessions().unwrap();
+        assert_eq!(
+            filter.where_clause(),
+            " where user_id in (select id from users where $1 = any(roles)) and creation_time < to_timestamp($2)::timestamp"
+        );
+        assert!(by_age.oauth_sessions().is_none());
+    }
+}
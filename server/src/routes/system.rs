@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Router,
+};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    auth::ApiAuth,
+    broadcast::{self, CacheEvent},
+    routes::login_history,
+    ApiResponse, AppResult, AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/info", get(info))
+        .route("/tasks/:name", post(run_task))
+}
+
+#[derive(Serialize)]
+struct EntityCounts {
+    users: i64,
+    applications: i64,
+    application_groups: i64,
+    sessions: i64,
+}
+
+#[derive(Serialize)]
+struct SystemInfo {
+    version: &'static str,
+    commit: &'static str,
+    schema: Option<i32>,
+    counts: EntityCounts,
+    uptime: u64,
+}
+
+#[instrument(skip_all, name = "system_info")]
+async fn info(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+) -> AppResult<ApiResponse<SystemInfo>> {
+    auth.check_admin()?;
+    let conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached("select max(version) from refinery_schema_history")
+        .await?;
+    let schema: Option<i32> = conn.query_one(&stmt, &[]).await?.get(0);
+    let stmt = conn
+        .prepare_cached("select (select count(*) from users) as users, (select count(*) from applications) as applications, (select count(*) from application_groups) as application_groups, (select count(*) from sessions) as sessions")
+        .await?;
+    let row = conn.query_one(&stmt, &[]).await?;
+    Ok(ApiResponse(SystemInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: env!("GIT_COMMIT"),
+        schema,
+        counts: EntityCounts {
+            users: row.get("users"),
+            applications: row.get("applications"),
+            application_groups: row.get("application_groups"),
+            sessions: row.get("sessions"),
+        },
+        uptime: state.uptime().as_secs(),
+    }))
+}
+
+#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SystemTask {
+    #[display("flush-caches")]
+    FlushCaches,
+    #[display("purge-sessions")]
+    PurgeSessions,
+}
+
+#[derive(Serialize)]
+struct TaskResult {
+    task: SystemTask,
+    message: String,
+    duration_ms: u64,
+}
+
+#[instrument(skip_all, name = "system_task", fields(task = %task))]
+async fn run_task(
+    State(state): State<AppState>,
+    ApiAuth(auth): ApiAuth,
+    Path(task): Path<SystemTask>,
+) -> AppResult<ApiResponse<TaskResult>> {
+    auth.check_admin()?;
+    let started = Instant::now();
+    let conn = state.conn().await?;
+    let message = match task {
+        SystemTask::FlushCaches => {
+            state.features().reload(&conn).await?;
+            state.maintenance().reload(&conn).await?;
+            state.sessions().clear();
+            broadcast::send(&conn, CacheEvent::All).await?;
+            "Reloaded feature flags, maintenance state and session cache on all replicas"
+                .to_string()
+        }
+        SystemTask::PurgeSessions => {
+            let lifetimes = &state.config().session;
+            let stmt = conn
+                .prepare_cached("delete from sessions where creation_time < now() - make_interval(secs => $1) or last_used < now() - make_interval(secs => $2)")
+                .await?;
+            let purged = conn
+                .execute(
+                    &stmt,
+                    &[&(lifetimes.lifetime as f64), &(lifetimes.idle as f64)],
+                )
+                .await?;
+            let history = login_history::purge(&conn, lifetimes.history).await?;
+            format!("Purged {purged} expired sessions and {history} login history entries")
+        }
+    };
+    let duration_ms = started.elapsed().as_millis() as u64;
+    tracing::warn!(user = %auth.user, duration_ms, "{message}");
+    Ok(ApiResponse(TaskResult {
+        task,
+        message,
+        duration_ms,
+    }))
+}
@@ -1,7 +1,7 @@
 use axum::{
-    extract::{FromRequestParts, Path, Query, State},
-    http::{request::Parts, StatusCode},
-    routing::get,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, put},
     Router,
 };
 use deadpool_postgres::GenericClient;
@@ -12,16 +12,30 @@ use uuid::Uuid;
 
 use crate::{
     auth::{ApiAuth, UserRole},
-    error::{Error, ErrorKind},
-    utils::password::hash_password,
-    ApiJson, ApiResponse, AppResult, AppState, PAGE_LIMIT,
+    error::{ApiError, ErrorKind},
+    routes::{
+        login_history::{self, LoginRecord},
+        pagination::{Page, Pagination},
+        password,
+    },
+    telemetry::audit,
+    utils::{
+        password::{check_password_policy, hash_password},
+        query::Filter,
+    },
+    ApiJson, ApiQuery, ApiResponse, AppResult, AppState,
 };
 
 pub fn router() -> Router<AppState> {
+    Router::new().route("/@me", get(me))
+}
+
+pub fn admin_router() -> Router<AppState> {
     Router::new()
-        .route("/@me", get(me))
         .route("/", get(list).post(create))
         .route("/:id", get(user).delete(delete).put(replace))
+        .route("/:id/logins", get(logins))
+        .route("/:id/password", put(reset_password))
 }
 
 #[derive(Serialize)]
@@ -29,41 +43,7 @@ struct EncodedUser {
     name: String,
     roles: Vec<UserRole>,
     require_password_reset: bool,
-}
-
-fn per_page_default() -> u16 {
-    25
-}
-
-fn page_default() -> u8 {
-    1
-}
-
-#[derive(Deserialize)]
-pub struct Pagination {
-    #[serde(default = "page_default")]
-    pub page: u8,
-    #[serde(default = "per_page_default")]
-    pub per_page: u16,
-}
-
-impl Pagination {
-    pub fn limit(&self, max: u16) -> i64 {
-        self.per_page.min(max) as i64
-    }
-
-    pub fn offset(&self, max: u16) -> i64 {
-        (self.limit(max) as i64).saturating_mul((self.page.saturating_sub(1)) as i64)
-    }
-}
-
-#[axum::async_trait]
-impl<S: Send + Sync> FromRequestParts<S> for Pagination {
-    type Rejection = Error;
-    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let query: Pagination = Query::from_request_parts(parts, state).await?.0;
-        Ok(query)
-    }
+    avatar: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -96,13 +76,21 @@ async fn me(
 ) -> AppResult<ApiResponse<EncodedUser>> {
     let conn = state.conn().await?;
     let stmt = conn
-        .prepare_cached("select name,roles,require_password_reset from users where id = $1")
+        .prepare_cached(
+            "select name,email,roles,require_password_reset,avatar from users where id = $1",
+        )
         .await?;
     let row = conn.query_one(&stmt, &[&info.user]).await?;
+    let avatar = state.media().avatar_url(
+        state.auth(),
+        row.get::<_, Option<&str>>("avatar"),
+        row.get::<_, Option<&str>>("email"),
+    )?;
     Ok(ApiResponse(EncodedUser {
         name: row.get("name"),
         roles: row.get("roles"),
         require_password_reset: row.get("require_password_reset"),
+        avatar,
     }))
 }
 
@@ -121,6 +109,12 @@ async fn create(
     ApiJson(payload): ApiJson<CreatePayload>,
 ) -> AppResult<ApiResponse<()>> {
     info.check_admin()?;
+    check_password_policy(
+        &state.config().password,
+        &payload.password,
+        Some(&payload.name),
+    )?;
+    state.breach().ensure_safe(&payload.password).await?;
     let conn = state.conn().await?;
     let hashed =
         tokio::task::spawn_blocking(move || hash_password(payload.password.as_bytes())).await??;
@@ -152,11 +146,9 @@ async fn user(
     let stmt = conn
         .prepare_cached("select * from users where id = $1")
         .await?;
-    let row = conn.query_opt(&stmt, &[&id]).await?;
-    match row {
-        Some(row) => Ok(ApiResponse(admin_from_row(row))),
-        None => Err(ErrorKind::not_found().into()),
-    }
+    let Some(row) = conn.query_opt(&stmt, &[&id]).await? else { return Err(ErrorKind::not_found().into()) };
+    audit::read(&state.config().audit, &info.user, "user", Some(&id), 1);
+    Ok(ApiResponse(admin_from_row(row)))
 }
 
 #[instrument(skip_all name = "is_last_admin")]
@@ -195,27 +187,152 @@ async fn delete(
     }
 }
 
+#[instrument(skip_all, name = "user_logins")]
+async fn logins(
+    State(state): State<AppState>,
+    ApiAuth(info): ApiAuth,
+    Path(id): Path<Uuid>,
+    pagination: Pagination,
+) -> AppResult<ApiResponse<Page<LoginRecord>>> {
+    info.check_admin()?;
+    let limits = state.config().page.limits("logins");
+    let conn = state.conn().await?;
+    let page = login_history::list(&conn, &id, &pagination, limits).await?;
+    audit::read(
+        &state.config().audit,
+        &info.user,
+        "login_history",
+        Some(&id),
+        page.items.len(),
+    );
+    Ok(ApiResponse(page))
+}
+
+#[derive(Deserialize)]
+struct ResetPasswordPayload {
+    password: String,
+}
+
+#[instrument(skip_all, name = "user_reset_password")]
+async fn reset_password(
+    State(state): State<AppState>,
+    ApiAuth(info): ApiAuth,
+    Path(id): Path<Uuid>,
+    ApiJson(payload): ApiJson<ResetPasswordPayload>,
+) -> AppResult<ApiResponse<()>> {
+    info.check_admin()?;
+    let mut conn = state.conn().await?;
+    let stmt = conn
+        .prepare_cached("select name from users where id = $1")
+        .await?;
+    let Some(row) = conn.query_opt(&stmt, &[&id]).await? else { return Err(ErrorKind::not_found().into()) };
+    let name: String = row.get("name");
+    check_password_policy(&state.config().password, &payload.password, Some(&name))?;
+    state.breach().ensure_safe(&payload.password).await?;
+    let hashed =
+        tokio::task::spawn_blocking(move || hash_password(payload.password.as_bytes())).await??;
+    let keep = (id == info.user).then_some(info.id);
+    let tx = conn.transaction().await?;
+    password::change_password(
+        &tx,
+        state.sessions(),
+        &info.user,
+        &id,
+        &hashed,
+        keep.as_ref(),
+        state.config().password.revoke,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(ApiResponse(()))
+}
+
+#[derive(Deserialize)]
+struct UserFilters {
+    search: Option<String>,
+    active: Option<bool>,
+    customer: Option<bool>,
+    role: Option<UserRole>,
+}
+
+impl UserFilters {
+    fn filter(self) -> Filter {
+        let mut filter = Filter::new();
+        if let Some(search) = self.search.filter(|s| !s.is_empty()) {
+            filter.search(&["name", "email"], &search);
+        }
+        if let Some(active) = self.active {
+            filter.eq("active", active);
+        }
+        if let Some(customer) = self.customer {
+            filter.eq("customer", customer);
+        }
+        if let Some(role) = self.role {
+            filter.contains("roles", role);
+        }
+        filter
+    }
+}
+
 #[instrument(skip_all name = "user_list")]
 async fn list(
     State(state): State<AppState>,
     ApiAuth(info): ApiAuth,
     pagination: Pagination,
-) -> AppResult<ApiResponse<Vec<AdminUser>>> {
+    ApiQuery(filters): ApiQuery<UserFilters>,
+) -> AppResult<ApiResponse<Page<AdminUser>>> {
     info.check_admin()?;
+    let limits = state.config().page.limits("users");
     let conn = state.conn().await?;
+    let mut filter = filters.filter();
     let stmt = conn
-        .prepare_cached("select * from users limit $1 offset $2")
+        .prepare_cached(&format!(
+            "select count(*) from users{}",
+            filter.where_clause()
+        ))
         .await?;
-    let rows = conn
-        .query(
-            &stmt,
-            &[
-                &pagination.limit(PAGE_LIMIT),
-                &pagination.offset(PAGE_LIMIT),
-            ],
-        )
+    let total: i64 = conn.query_one(&stmt, &filter.params()).await?.get(0);
+    if let Some(cursor) = &pagination.cursor {
+        let cursor: Uuid = cursor
+            .parse()
+            .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "Invalid cursor"))?;
+        filter.gt("id", cursor);
+        let limit = filter.bind(pagination.limit(limits));
+        let stmt = conn
+            .prepare_cached(&format!(
+                "select * from users{} order by id limit {limit}",
+                filter.where_clause()
+            ))
+            .await?;
+        let rows = conn.query(&stmt, &filter.params()).await?;
+        audit::read(&state.config().audit, &info.user, "users", None, rows.len());
+        let users = rows.into_iter().map(admin_from_row).collect();
+        return Ok(ApiResponse(Page::from_cursor(
+            users,
+            total,
+            &pagination,
+            limits,
+            |user| user.id.to_string(),
+        )));
+    }
+    let limit = filter.bind(pagination.limit(limits));
+    let offset = filter.bind(pagination.offset(limits));
+    let stmt = conn
+        .prepare_cached(&format!(
+            "select * from users{} order by id limit {limit} offset {offset}",
+            filter.where_clause()
+        ))
         .await?;
-    Ok(ApiResponse(rows.into_iter().map(admin_from_row).collect()))
+    let rows = conn.query(&stmt, &filter.params()).await?;
+    audit::read(&state.config().audit, &info.user, "users", None, rows.len());
+    let users = rows.into_iter().map(admin_from_row).collect();
+    Ok(ApiResponse(
+        Page::from_offset(users, total, &pagination, limits).with_cursor(
+            &pagination,
+            limits,
+            |user| user.id.to_string(),
+        ),
+    ))
 }
 
 #[derive(Debug, Deserialize)]
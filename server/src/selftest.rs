@@ -0,0 +1,66 @@
+use jsonwebtoken::TokenData;
+use uuid::Uuid;
+
+use crate::{
+    auth::{jwt_header, BaseClaims},
+    AppState,
+};
+
+async fn check_jwt(state: &AppState) -> Result<(), String> {
+    let auth = state.auth();
+    let subject = Uuid::nil();
+    let claims = BaseClaims::new(auth.issuer(), subject, Uuid::nil());
+    let token = jsonwebtoken::encode(&jwt_header(), &claims, auth.encoding())
+        .map_err(|err| format!("signing failed: {err}"))?;
+    let decoded: TokenData<BaseClaims<Uuid>> =
+        jsonwebtoken::decode(&token, auth.decoding(), auth.validation())
+            .map_err(|err| format!("verification failed: {err}"))?;
+    if decoded.claims.sub != subject {
+        return Err("verified token has a different subject".to_string());
+    }
+    Ok(())
+}
+
+async fn check_database(state: &AppState) -> Result<(), String> {
+    let mut conn = state
+        .conn_direct()
+        .await
+        .map_err(|err| format!("no connection: {err}"))?;
+    let tx = conn
+        .transaction()
+        .await
+        .map_err(|err| format!("begin failed: {err}"))?;
+    tx.query_one("select 1", &[])
+        .await
+        .map_err(|err| format!("query failed: {err}"))?;
+    tx.rollback()
+        .await
+        .map_err(|err| format!("rollback failed: {err}"))
+}
+
+async fn check_media(state: &AppState) -> Result<(), String> {
+    state
+        .media()
+        .probe()
+        .await
+        .map_err(|err| format!("media storage is not writable: {err}"))
+}
+
+pub async fn run(state: &AppState) -> bool {
+    let checks = [
+        ("jwt", check_jwt(state).await),
+        ("database", check_database(state).await),
+        ("media", check_media(state).await),
+    ];
+    let mut passed = true;
+    for (name, result) in checks {
+        match result {
+            Ok(()) => tracing::info!(check = name, "Self-test passed"),
+            Err(err) => {
+                tracing::error!(check = name, "Self-test failed: {err}");
+                passed = false;
+            }
+        }
+    }
+    passed
+}
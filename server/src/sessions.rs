@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use deadpool_postgres::GenericClient;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::AppResult;
+
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionState {
+    pub exists: bool,
+    pub active: bool,
+    pub not_before: Option<u64>,
+}
+
+impl SessionState {
+    pub fn accepts(&self, issued_at: u64) -> bool {
+        self.exists && self.active && self.not_before.map_or(true, |nb| issued_at >= nb)
+    }
+}
+
+pub struct SessionCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<Uuid, (Instant, SessionState)>>,
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn cached(&self, session: &Uuid) -> Option<SessionState> {
+        let entries = self.entries.read().expect("Session cache lock poisoned");
+        match entries.get(session) {
+            Some((checked, state)) if checked.elapsed() < self.ttl => Some(*state),
+            _ => None,
+        }
+    }
+
+    fn store(&self, session: Uuid, state: SessionState) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.write().expect("Session cache lock poisoned");
+        if entries.len() >= MAX_ENTRIES {
+            let ttl = self.ttl;
+            entries.retain(|_, (checked, _)| checked.elapsed() < ttl);
+        }
+        if entries.len() < MAX_ENTRIES {
+            entries.insert(session, (Instant::now(), state));
+        }
+    }
+
+    #[instrument(skip_all, name = "session_state")]
+    pub async fn state(
+        &self,
+        client: &impl GenericClient,
+        session: &Uuid,
+        user: &Uuid,
+    ) -> AppResult<SessionState> {
+        if let Some(state) = self.cached(session) {
+            return Ok(state);
+        }
+        let stmt = client
+            .prepare_cached("select s.id is not null as exists,u.active,floor(extract(epoch from nullif(u.not_before, s.not_before_exempt)))::bigint as not_before from users u left join sessions s on s.id = $1 and s.user_id = u.id where u.id = $2")
+            .await?;
+        let state = match client.query_opt(&stmt, &[session, user]).await? {
+            Some(row) => SessionState {
+                exists: row.get("exists"),
+                active: row.get("active"),
+                not_before: row
+                    .get::<_, Option<i64>>("not_before")
+                    .map(|nb| nb.max(0) as u64),
+            },
+            None => SessionState {
+                exists: false,
+                active: false,
+                not_before: None,
+            },
+        };
+        self.store(*session, state);
+        Ok(state)
+    }
+
+    pub fn invalidate(&self, session: &Uuid) {
+        self.entries
+            .write()
+            .expect("Session cache lock poisoned")
+            .remove(session);
+    }
+
+    pub fn clear(&self) {
+        self.entries
+            .write()
+            .expect("Session cache lock poisoned")
+            .clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionState;
+
+    #[test]
+    fn rejects_tokens_issued_before_cutoff() {
+        let state = SessionState {
+            exists: true,
+            active: true,
+            not_before: Some(100),
+        };
+        assert!(state.accepts(100));
+        assert!(!state.accepts(99));
+        assert!(!SessionState {
+            active: false,
+            ..state
+        }
+        .accepts(200));
+        assert!(!SessionState {
+            exists: false,
+            ..state
+        }
+        .accepts(200));
+    }
+}
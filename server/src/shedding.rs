@@ -0,0 +1,87 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use axum::{
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{error::ApiError, AppResult, AppState};
+
+pub struct LoadShedder {
+    depth: usize,
+    shed: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl LoadShedder {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            shed: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn overloaded(&self, waiting: usize) -> bool {
+        self.depth > 0 && waiting >= self.depth
+    }
+
+    fn record(&self, group: &'static str) {
+        *self
+            .shed
+            .lock()
+            .expect("Load shedder lock poisoned")
+            .entry(group)
+            .or_default() += 1;
+    }
+
+    pub fn counters(&self) -> Vec<(&'static str, u64)> {
+        self.shed
+            .lock()
+            .expect("Load shedder lock poisoned")
+            .iter()
+            .map(|(group, count)| (*group, *count))
+            .collect()
+    }
+}
+
+pub async fn guard<B>(
+    State((state, group)): State<(AppState, &'static str)>,
+    request: Request<B>,
+    next: Next<B>,
+) -> AppResult<Response> {
+    let waiting = state.pool_waiting();
+    let shedder = state.shedder();
+    if shedder.overloaded(waiting) {
+        shedder.record(group);
+        tracing::warn!(group, waiting, "Shedding request under pool exhaustion");
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Server is under heavy load, try again later",
+        )
+        .into());
+    }
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoadShedder;
+
+    #[test]
+    fn sheds_only_past_depth() {
+        let shedder = LoadShedder::new(4);
+        assert!(!shedder.overloaded(3));
+        assert!(shedder.overloaded(4));
+        assert!(!LoadShedder::new(0).overloaded(1000));
+    }
+
+    #[test]
+    fn counts_per_group() {
+        let shedder = LoadShedder::new(1);
+        shedder.record("users");
+        shedder.record("admin");
+        shedder.record("users");
+        assert_eq!(shedder.counters(), vec![("admin", 1), ("users", 2)]);
+    }
+}
@@ -1,8 +1,21 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use deadpool_postgres::{Object, Pool};
+use deadpool_postgres::{Object, Pool, PoolError, Status};
 
-use crate::auth::AuthState;
+use crate::{
+    auth::AuthState,
+    chaos::Chaos,
+    config::AuthentraConfiguration,
+    features::{Feature, FeatureFlags},
+    maintenance::Maintenance,
+    media::Media,
+    sessions::SessionCache,
+    shedding::LoadShedder,
+    utils::breach::BreachCheck,
+};
 
 #[derive(Clone)]
 pub struct AppState(Arc<InternalState>);
@@ -10,18 +23,106 @@ pub struct AppState(Arc<InternalState>);
 pub(super) struct InternalState {
     pool: Pool,
     auth: AuthState,
+    config: AuthentraConfiguration,
+    features: FeatureFlags,
+    maintenance: Maintenance,
+    media: Media,
+    sessions: SessionCache,
+    breach: BreachCheck,
+    shedder: LoadShedder,
+    chaos: Chaos,
+    started: Instant,
+}
+
+/// Everything the application state is built from.
+pub struct StateParts {
+    pub pool: Pool,
+    pub auth: AuthState,
+    pub config: AuthentraConfiguration,
+    pub features: FeatureFlags,
+    pub maintenance: Maintenance,
+    pub media: Media,
+    pub sessions: SessionCache,
+    pub breach: BreachCheck,
+    pub shedder: LoadShedder,
+    pub chaos: Chaos,
 }
 
 impl AppState {
-    pub fn new(pool: Pool, auth: AuthState) -> Self {
-        Self(Arc::new(InternalState { pool, auth }))
+    pub fn new(parts: StateParts) -> Self {
+        Self(Arc::new(InternalState {
+            pool: parts.pool,
+            auth: parts.auth,
+            config: parts.config,
+            features: parts.features,
+            maintenance: parts.maintenance,
+            media: parts.media,
+            sessions: parts.sessions,
+            breach: parts.breach,
+            shedder: parts.shedder,
+            chaos: parts.chaos,
+            started: Instant::now(),
+        }))
     }
 
-    pub async fn conn(&self) -> Result<Object, deadpool_postgres::PoolError> {
+    pub async fn conn(&self) -> Result<Object, PoolError> {
+        if self.0.chaos.inject("storage").await {
+            return Err(PoolError::Closed);
+        }
         self.0.pool.get().await
     }
 
+    /// Gets a connection without fault injection, for checks that must
+    /// report the real state of the database.
+    pub async fn conn_direct(&self) -> Result<Object, PoolError> {
+        self.0.pool.get().await
+    }
+
+    pub fn pool_status(&self) -> Status {
+        self.0.pool.status()
+    }
+
+    pub fn pool_waiting(&self) -> usize {
+        self.pool_status().available.min(0).unsigned_abs()
+    }
+
     pub fn auth(&self) -> &AuthState {
         &self.0.auth
     }
+
+    pub fn config(&self) -> &AuthentraConfiguration {
+        &self.0.config
+    }
+
+    pub fn features(&self) -> &FeatureFlags {
+        &self.0.features
+    }
+
+    pub fn feature_enabled(&self, feature: Feature) -> bool {
+        self.0.features.enabled(feature)
+    }
+
+    pub fn maintenance(&self) -> &Maintenance {
+        &self.0.maintenance
+    }
+
+    pub fn media(&self) -> &Media {
+        &self.0.media
+    }
+
+    pub fn sessions(&self) -> &SessionCache {
+        &self.0.sessions
+    }
+
+    pub fn breach(&self) -> &BreachCheck {
+        &self.0.breach
+    }
+
+    pub fn shedder(&self) -> &LoadShedder {
+        &self.0.shedder
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.0.started.elapsed()
+    }
 }
@@ -1,3 +1,6 @@
+pub mod audit;
+pub mod decision;
+pub mod metrics;
 pub mod middleware;
 mod otel;
 
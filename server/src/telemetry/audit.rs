@@ -0,0 +1,61 @@
+use uuid::Uuid;
+
+use crate::config::AuditConfiguration;
+
+pub const TARGET: &str = "authentra::audit";
+
+fn sampled(sample: f64, roll: f64) -> bool {
+    roll < sample
+}
+
+pub fn read(
+    config: &AuditConfiguration,
+    actor: &Uuid,
+    resource: &'static str,
+    subject: Option<&Uuid>,
+    count: usize,
+) {
+    if !config.reads || !sampled(config.sample, rand::random()) {
+        return;
+    }
+    tracing::info!(
+        target: TARGET,
+        action = "read",
+        actor = %actor,
+        resource,
+        subject = subject.map(|s| s.to_string()),
+        count,
+        sample = config.sample,
+    );
+}
+
+/// Records a change. Writes are always audited, regardless of sampling.
+pub fn write(
+    actor: &Uuid,
+    action: &'static str,
+    resource: &'static str,
+    subject: Option<&Uuid>,
+    count: usize,
+) {
+    tracing::info!(
+        target: TARGET,
+        action,
+        actor = %actor,
+        resource,
+        subject = subject.map(|s| s.to_string()),
+        count,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sampled;
+
+    #[test]
+    fn sampling_bounds() {
+        assert!(sampled(1.0, 0.999));
+        assert!(!sampled(0.0, 0.0));
+        assert!(sampled(0.25, 0.1));
+        assert!(!sampled(0.25, 0.5));
+    }
+}
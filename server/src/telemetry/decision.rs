@@ -0,0 +1,64 @@
+use std::time::Instant;
+
+use uuid::Uuid;
+
+pub const TARGET: &str = "authentra::decision";
+
+pub struct Decision {
+    kind: &'static str,
+    started: Instant,
+    subject: Option<Uuid>,
+    steps: Vec<(&'static str, bool)>,
+}
+
+impl Decision {
+    pub fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            started: Instant::now(),
+            subject: None,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn subject(&mut self, subject: Uuid) {
+        self.subject = Some(subject);
+    }
+
+    pub fn step(&mut self, name: &'static str, passed: bool) -> bool {
+        self.steps.push((name, passed));
+        passed
+    }
+
+    fn steps(&self) -> String {
+        self.steps
+            .iter()
+            .map(|(name, passed)| format!("{name}={}", if *passed { "pass" } else { "fail" }))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn log(self, verdict: &'static str, reason: Option<&'static str>) {
+        tracing::info!(
+            target: TARGET,
+            kind = self.kind,
+            subject = self.subject.map(|s| s.to_string()),
+            steps = self.steps(),
+            duration_ms = self.started.elapsed().as_millis() as u64,
+            verdict,
+            reason,
+        );
+    }
+
+    pub fn allow(self) {
+        self.log("allow", None);
+    }
+
+    pub fn deny(self, reason: &'static str) {
+        self.log("deny", Some(reason));
+    }
+
+    pub fn prompt(self, reason: &'static str) {
+        self.log("prompt", Some(reason));
+    }
+}
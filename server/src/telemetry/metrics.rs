@@ -0,0 +1,42 @@
+use std::{fmt::Write, net::SocketAddr};
+
+use axum::{extract::State, routing::get, Router, Server};
+
+use crate::AppState;
+
+fn render(state: &AppState) -> String {
+    let status = state.pool_status();
+    let mut out = String::new();
+    let _ = writeln!(out, "# TYPE authentra_pool_size gauge");
+    let _ = writeln!(out, "authentra_pool_size {}", status.size);
+    let _ = writeln!(out, "# TYPE authentra_pool_max_size gauge");
+    let _ = writeln!(out, "authentra_pool_max_size {}", status.max_size);
+    let _ = writeln!(out, "# TYPE authentra_pool_waiting gauge");
+    let _ = writeln!(out, "authentra_pool_waiting {}", state.pool_waiting());
+    let _ = writeln!(out, "# TYPE authentra_shed_requests_total counter");
+    for (group, count) in state.shedder().counters() {
+        let _ = writeln!(
+            out,
+            "authentra_shed_requests_total{{group=\"{group}\"}} {count}"
+        );
+    }
+    let _ = writeln!(out, "# TYPE authentra_uptime_seconds gauge");
+    let _ = writeln!(out, "authentra_uptime_seconds {}", state.uptime().as_secs());
+    out
+}
+
+async fn metrics(State(state): State<AppState>) -> String {
+    render(&state)
+}
+
+pub async fn serve(address: SocketAddr, state: AppState) {
+    let router = Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(state);
+    if let Err(err) = Server::bind(&address)
+        .serve(router.into_make_service())
+        .await
+    {
+        tracing::error!("Metrics server failed: {err}");
+    }
+}
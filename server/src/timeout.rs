@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{error::ApiError, AppResult};
+
+pub async fn guard<B>(
+    State(budget): State<Duration>,
+    request: Request<B>,
+    next: Next<B>,
+) -> AppResult<Response> {
+    let path = request.uri().path().to_string();
+    match tokio::time::timeout(budget, next.run(request)).await {
+        Ok(response) => Ok(response),
+        Err(_) => {
+            tracing::warn!(
+                path,
+                budget_ms = budget.as_millis() as u64,
+                "Request timed out"
+            );
+            Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Request timed out").into())
+        }
+    }
+}
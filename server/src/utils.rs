@@ -1,2 +1,5 @@
+pub mod breach;
 pub mod id_gen;
+pub mod network;
 pub mod password;
+pub mod query;
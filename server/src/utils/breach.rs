@@ -0,0 +1,322 @@
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use tracing::instrument;
+
+use crate::{chaos::Chaos, config::PasswordConfiguration, error::ApiError, AppResult};
+
+const PWNED_RANGE_URL: &str = "https://api.pwnedpasswords.com/range";
+const HASH_LENGTH: usize = 40;
+const BLOOM_MAGIC: &[u8; 8] = b"AUTHBLM1";
+const BLOOM_HEADER: u64 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreachProvider {
+    None,
+    Pwned,
+    Local,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailMode {
+    Open,
+    Closed,
+}
+
+#[axum::async_trait]
+pub trait BreachChecker: Send + Sync {
+    async fn is_breached(&self, hash: &str) -> io::Result<bool>;
+}
+
+fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect()
+}
+
+pub struct PwnedPasswords {
+    client: reqwest::Client,
+    chaos: Chaos,
+}
+
+impl PwnedPasswords {
+    pub fn new(chaos: Chaos) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            chaos,
+        }
+    }
+}
+
+fn range_contains(body: &str, suffix: &str) -> bool {
+    body.lines().any(|line| match line.trim().split_once(':') {
+        Some((candidate, count)) => candidate.eq_ignore_ascii_case(suffix) && count.trim() != "0",
+        None => false,
+    })
+}
+
+#[axum::async_trait]
+impl BreachChecker for PwnedPasswords {
+    async fn is_breached(&self, hash: &str) -> io::Result<bool> {
+        if self.chaos.inject("pwned").await {
+            return Err(io::Error::new(io::ErrorKind::Other, "Injected fault"));
+        }
+        let (prefix, suffix) = hash.split_at(5);
+        let body = self
+            .client
+            .get(format!("{PWNED_RANGE_URL}/{prefix}"))
+            .header("Add-Padding", "true")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+            .text()
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        Ok(range_contains(&body, suffix))
+    }
+}
+
+/// Checks a local breach list, either a bloom filter or a sorted SHA-1 list.
+///
+/// A bloom filter starts with `AUTHBLM1`, followed by the number of hash
+/// functions as a little-endian u32 and the bit array. Bit positions are
+/// derived from the first 128 bits of the SHA-1 hash by double hashing.
+/// Any other file is treated as a sorted list of uppercase SHA-1 hashes, one
+/// per line, as in the Pwned Passwords downloads, and is binary searched.
+pub struct LocalBreachList {
+    path: PathBuf,
+}
+
+impl LocalBreachList {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+fn line_at(reader: &mut BufReader<File>, pos: u64) -> io::Result<Option<(u64, String)>> {
+    let mut start = pos;
+    if pos > 0 {
+        reader.seek(SeekFrom::Start(pos - 1))?;
+        let mut skipped = Vec::new();
+        start = pos - 1 + reader.read_until(b'\n', &mut skipped)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some((start, line)))
+}
+
+fn sorted_list_contains(path: &Path, hash: &str) -> io::Result<bool> {
+    let file = File::open(path)?;
+    let mut hi = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut lo = 0;
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let Some((start, line)) = line_at(&mut reader, mid)? else {
+            hi = mid;
+            continue;
+        };
+        let candidate = line.get(..HASH_LENGTH).unwrap_or(line.trim_end());
+        match candidate.to_ascii_uppercase().as_str().cmp(hash) {
+            Ordering::Equal => return Ok(true),
+            Ordering::Less => lo = start + line.len() as u64,
+            Ordering::Greater => hi = mid,
+        }
+    }
+    Ok(false)
+}
+
+fn bloom_hashes(hash: &str) -> Option<(u64, u64)> {
+    let first = u64::from_str_radix(hash.get(..16)?, 16).ok()?;
+    let second = u64::from_str_radix(hash.get(16..32)?, 16).ok()?;
+    Some((first, second))
+}
+
+fn bloom_bits(hash: &str, functions: u32, bits: u64) -> io::Result<impl Iterator<Item = u64>> {
+    let (first, second) = bloom_hashes(hash)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid SHA-1 hash"))?;
+    Ok((0..functions as u64).map(move |i| first.wrapping_add(i.wrapping_mul(second)) % bits))
+}
+
+fn bloom_contains(mut file: File, hash: &str) -> io::Result<bool> {
+    let len = file.metadata()?.len();
+    let mut functions = [0; 4];
+    file.read_exact(&mut functions)?;
+    let bits = len.saturating_sub(BLOOM_HEADER) * 8;
+    if bits == 0 {
+        return Ok(false);
+    }
+    for bit in bloom_bits(hash, u32::from_le_bytes(functions), bits)? {
+        let mut byte = [0; 1];
+        file.seek(SeekFrom::Start(BLOOM_HEADER + bit / 8))?;
+        file.read_exact(&mut byte)?;
+        if byte[0] & (1 << (bit % 8)) == 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn local_list_contains(path: &Path, hash: &str) -> io::Result<bool> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 8];
+    if file.read_exact(&mut magic).is_ok() && &magic == BLOOM_MAGIC {
+        return bloom_contains(file, hash);
+    }
+    sorted_list_contains(path, hash)
+}
+
+#[axum::async_trait]
+impl BreachChecker for LocalBreachList {
+    async fn is_breached(&self, hash: &str) -> io::Result<bool> {
+        let path = self.path.clone();
+        let hash = hash.to_string();
+        tokio::task::spawn_blocking(move || local_list_contains(&path, &hash))
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    }
+}
+
+pub struct BreachCheck {
+    checker: Option<Box<dyn BreachChecker>>,
+    timeout: Duration,
+    fail: FailMode,
+}
+
+impl BreachCheck {
+    pub fn new(policy: &PasswordConfiguration, chaos: Chaos) -> Self {
+        let checker: Option<Box<dyn BreachChecker>> = match policy.breach {
+            BreachProvider::None => None,
+            BreachProvider::Pwned => Some(Box::new(PwnedPasswords::new(chaos))),
+            BreachProvider::Local => policy
+                .list
+                .clone()
+                .map(|path| Box::new(LocalBreachList::new(path)) as Box<dyn BreachChecker>),
+        };
+        Self {
+            checker,
+            timeout: Duration::from_millis(policy.timeout),
+            fail: policy.fail,
+        }
+    }
+
+    #[instrument(skip_all, name = "breach_check")]
+    pub async fn is_breached(&self, password: &str) -> AppResult<bool> {
+        let Some(checker) = &self.checker else { return Ok(false) };
+        let hash = sha1_hex(password);
+        let error = match tokio::time::timeout(self.timeout, checker.is_breached(&hash)).await {
+            Ok(Ok(breached)) => return Ok(breached),
+            Ok(Err(err)) => err.to_string(),
+            Err(_) => "timed out".to_string(),
+        };
+        tracing::warn!(error, fail = ?self.fail, "Password breach check failed");
+        match self.fail {
+            FailMode::Open => Ok(false),
+            FailMode::Closed => Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Password breach check unavailable",
+            )
+            .into()),
+        }
+    }
+
+    pub async fn ensure_safe(&self, password: &str) -> AppResult<()> {
+        if self.is_breached(password).await? {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Password appears in a known data breach",
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{
+        bloom_bits, local_list_contains, range_contains, sha1_hex, sorted_list_contains,
+        BLOOM_MAGIC,
+    };
+
+    #[test]
+    fn hashes_like_pwned_passwords() {
+        assert_eq!(
+            sha1_hex("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+    }
+
+    #[test]
+    fn ignores_padding_entries() {
+        let body =
+            "1E4C9B93F3F0682250B6CF8331B7EE68FD8:3861493\r\nAAAA9B93F3F0682250B6CF8331B7EE68FD8:0";
+        assert!(range_contains(body, "1E4C9B93F3F0682250B6CF8331B7EE68FD8"));
+        assert!(!range_contains(body, "AAAA9B93F3F0682250B6CF8331B7EE68FD8"));
+    }
+
+    #[test]
+    fn searches_sorted_list() {
+        let mut hashes: Vec<String> = ["password", "123456", "letmein", "qwerty", "dragon"]
+            .iter()
+            .map(|p| sha1_hex(p))
+            .collect();
+        hashes.sort();
+        let path = std::env::temp_dir().join(format!("breach-{}.txt", rand::random::<u64>()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        for hash in &hashes {
+            write!(file, "{hash}:42\r\n").unwrap();
+        }
+        drop(file);
+        for hash in &hashes {
+            assert!(sorted_list_contains(&path, hash).unwrap());
+        }
+        assert!(!sorted_list_contains(&path, &sha1_hex("correct-Horse-battery-9")).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn checks_bloom_filter() {
+        let functions: u32 = 7;
+        let mut filter = vec![0u8; 512];
+        let bits = filter.len() as u64 * 8;
+        let breached: Vec<String> = ["password", "123456", "letmein"]
+            .iter()
+            .map(|p| sha1_hex(p))
+            .collect();
+        for hash in &breached {
+            for bit in bloom_bits(hash, functions, bits).unwrap() {
+                filter[(bit / 8) as usize] |= 1 << (bit % 8);
+            }
+        }
+        let path = std::env::temp_dir().join(format!("breach-{}.bloom", rand::random::<u64>()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(BLOOM_MAGIC).unwrap();
+        file.write_all(&functions.to_le_bytes()).unwrap();
+        file.write_all(&filter).unwrap();
+        drop(file);
+        for hash in &breached {
+            assert!(local_list_contains(&path, hash).unwrap());
+        }
+        assert!(!local_list_contains(&path, &sha1_hex("correct-Horse-battery-9")).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -3,8 +3,12 @@ use argon2::{
     password_hash::{Encoding, SaltString},
     Argon2, PasswordHash, PasswordHasher,
 };
+use axum::http::StatusCode;
 use once_cell::sync::Lazy;
 use rand::thread_rng;
+use serde::Serialize;
+
+use crate::{config::PasswordConfiguration, error::ApiError, AppResult};
 
 static ARGON2_INSTANCE: Lazy<Argon2> = Lazy::new(|| Argon2::default());
 
@@ -29,3 +33,126 @@ pub fn handle_result<T>(result: Result<T, ArgonError>) -> Result<Option<T>, Argo
         },
     }
 }
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordRule {
+    Length,
+    Classes,
+    ContainsUsername,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasswordStrength {
+    pub score: u8,
+    pub passed: bool,
+    pub failed_rules: Vec<PasswordRule>,
+}
+
+fn character_classes(password: &str) -> usize {
+    let lower = password.chars().any(|c| c.is_lowercase());
+    let upper = password.chars().any(|c| c.is_uppercase());
+    let digit = password.chars().any(|c| c.is_numeric());
+    let symbol = password.chars().any(|c| !c.is_alphanumeric());
+    [lower, upper, digit, symbol]
+        .into_iter()
+        .filter(|v| *v)
+        .count()
+}
+
+pub fn password_strength(
+    policy: &PasswordConfiguration,
+    password: &str,
+    username: Option<&str>,
+) -> PasswordStrength {
+    let length = password.chars().count();
+    let classes = character_classes(password);
+    let mut failed_rules = Vec::new();
+    if length < policy.length {
+        failed_rules.push(PasswordRule::Length);
+    }
+    if classes < policy.classes {
+        failed_rules.push(PasswordRule::Classes);
+    }
+    if let Some(username) = username.filter(|name| !name.is_empty()) {
+        if password.to_lowercase().contains(&username.to_lowercase()) {
+            failed_rules.push(PasswordRule::ContainsUsername);
+        }
+    }
+    let mut score = match length {
+        0..=7 => 0,
+        8..=11 => 1,
+        12..=15 => 2,
+        _ => 3,
+    };
+    if classes >= 3 {
+        score += 1;
+    }
+    if !failed_rules.is_empty() {
+        score = score.min(1);
+    }
+    PasswordStrength {
+        score,
+        passed: failed_rules.is_empty(),
+        failed_rules,
+    }
+}
+
+pub fn check_password_policy(
+    policy: &PasswordConfiguration,
+    password: &str,
+    username: Option<&str>,
+) -> AppResult<()> {
+    let strength = password_strength(policy, password, username);
+    if strength.passed {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Password does not satisfy the password policy",
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::PasswordConfiguration,
+        utils::breach::{BreachProvider, FailMode},
+    };
+
+    use super::{password_strength, PasswordRule};
+
+    const POLICY: PasswordConfiguration = PasswordConfiguration {
+        length: 8,
+        classes: 2,
+        revoke: true,
+        breach: BreachProvider::None,
+        list: None,
+        timeout: 2_000,
+        fail: FailMode::Open,
+    };
+
+    #[test]
+    fn strong_password_passes() {
+        let strength = password_strength(&POLICY, "correct-Horse-battery-9", Some("admin"));
+        assert!(strength.passed);
+        assert_eq!(strength.score, 4);
+    }
+
+    #[test]
+    fn weak_password_reports_rules() {
+        let strength = password_strength(&POLICY, "admin", Some("Admin"));
+        assert!(!strength.passed);
+        assert_eq!(
+            strength.failed_rules,
+            vec![
+                PasswordRule::Length,
+                PasswordRule::Classes,
+                PasswordRule::ContainsUsername
+            ]
+        );
+        assert_eq!(strength.score, 0);
+    }
+}
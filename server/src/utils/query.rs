@@ -0,0 +1,146 @@
+use tokio_postgres::types::ToSql;
+
+use crate::{auth::UserRole, utils::network::Cidr};
+
+type Param = Box<dyn ToSql + Sync + Send>;
+
+#[derive(Default)]
+pub struct Filter {
+    clauses: Vec<String>,
+    params: Vec<Param>,
+}
+
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(&mut self, value: impl ToSql + Sync + Send + 'static) -> String {
+        self.params.push(Box::new(value));
+        format!("${}", self.params.len())
+    }
+
+    pub fn eq(&mut self, column: &'static str, value: impl ToSql + Sync + Send + 'static) {
+        let param = self.bind(value);
+        self.clauses.push(format!("{column} = {param}"));
+    }
+
+    pub fn gt(&mut self, column: &'static str, value: impl ToSql + Sync + Send + 'static) {
+        let param = self.bind(value);
+        self.clauses.push(format!("{column} > {param}"));
+    }
+
+    pub fn before_timestamp(&mut self, column: &'static str, seconds: i64) {
+        let param = self.bind(seconds as f64);
+        self.clauses
+            .push(format!("{column} < to_timestamp({param})::timestamp"));
+    }
+
+    pub fn in_network(&mut self, column: &'static str, network: Cidr) {
+        let param = self.bind(network.to_string());
+        self.clauses
+            .push(format!("{column} <<= ({param}::text)::inet"));
+    }
+
+    pub fn user_with_role(&mut self, column: &'static str, role: UserRole) {
+        let param = self.bind(role);
+        self.clauses.push(format!(
+            "{column} in (select id from users where {param} = any(roles))"
+        ));
+    }
+
+    pub fn ge(&mut self, column: &'static str, value: impl ToSql + Sync + Send + 'static) {
+        let param = self.bind(value);
+        self.clauses.push(format!("{column} >= {param}"));
+    }
+
+    pub fn is_null(&mut self, column: &'static str) {
+        self.clauses.push(format!("{column} is null"));
+    }
+
+    pub fn is_not_null(&mut self, column: &'static str) {
+        self.clauses.push(format!("{column} is not null"));
+    }
+
+    pub fn contains(&mut self, column: &'static str, value: impl ToSql + Sync + Send + 'static) {
+        let param = self.bind(value);
+        self.clauses.push(format!("{param} = any({column})"));
+    }
+
+    pub fn search(&mut self, columns: &[&'static str], value: &str) {
+        let param = self.bind(format!("%{}%", escape_like(value)));
+        let matches: Vec<String> = columns
+            .iter()
+            .map(|column| format!("{column} ilike {param}"))
+            .collect();
+        self.clauses.push(format!("({})", matches.join(" or ")));
+    }
+
+    pub fn where_clause(&self) -> String {
+        if self.clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" where {}", self.clauses.join(" and "))
+        }
+    }
+
+    pub fn params(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.params
+            .iter()
+            .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_like, Filter};
+    use crate::auth::UserRole;
+
+    #[test]
+    fn numbers_parameters_in_order() {
+        let mut filter = Filter::new();
+        filter.eq("active", true);
+        filter.search(&["name", "email"], "bob");
+        let limit = filter.bind(10i64);
+        assert_eq!(
+            filter.where_clause(),
+            " where active = $1 and (name ilike $2 or email ilike $2)"
+        );
+        assert_eq!(limit, "$3");
+        assert_eq!(filter.params().len(), 3);
+    }
+
+    #[test]
+    fn typed_clauses() {
+        let mut filter = Filter::new();
+        filter.before_timestamp("creation_time", 1_700_000_000);
+        filter.in_network("address", "10.0.0.0/8".parse().unwrap());
+        filter.user_with_role("user_id", UserRole::Admin);
+        assert_eq!(
+            filter.where_clause(),
+            " where creation_time < to_timestamp($1)::timestamp and address <<= ($2::text)::inet and user_id in (select id from users where $3 = any(roles))"
+        );
+    }
+
+    #[test]
+    fn empty_filter_has_no_where_clause() {
+        assert_eq!(Filter::new().where_clause(), "");
+    }
+
+    #[test]
+    fn escapes_like_patterns() {
+        assert_eq!(escape_like(r"50%_off\"), r"50\%\_off\\");
+    }
+}